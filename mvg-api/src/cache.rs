@@ -0,0 +1,75 @@
+//! An on-disk cache for station lookups, enabled by the `station-cache`
+//! feature.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::station::{get_station_with_client, Location};
+
+/// How long a cached lookup is used before it is fetched again.
+pub const STATION_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Deserialize, Serialize)]
+struct CacheEntry {
+    /// Seconds since the Unix epoch.
+    fetched_at: u64,
+    locations: Vec<Location>,
+}
+
+/// Like [`get_station_with_client`], but answers from the JSON file at `path`
+/// if the same search, ignoring case, was fetched within
+/// [`STATION_CACHE_TTL`]. Fetched results are added to the file. A cache
+/// file that cannot be read or written is ignored.
+pub async fn get_station_cached(
+    client: &Client,
+    station_search: &str,
+    path: &Path,
+) -> Result<Vec<Location>, reqwest::Error> {
+    let key = station_search.trim().to_lowercase();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut cache: HashMap<String, CacheEntry> = fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    if let Some(entry) = cache.get(&key) {
+        if now.saturating_sub(entry.fetched_at) < STATION_CACHE_TTL.as_secs() {
+            return Ok(entry.locations.clone());
+        }
+    }
+
+    let locations = get_station_with_client(client, station_search).await?;
+    cache.retain(|_, entry| now.saturating_sub(entry.fetched_at) < STATION_CACHE_TTL.as_secs());
+    cache.insert(
+        key,
+        CacheEntry {
+            fetched_at: now,
+            locations: locations.clone(),
+        },
+    );
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Ok(content) = serde_json::to_string(&cache) {
+        let _ = fs::write(path, content);
+    }
+    Ok(locations)
+}
+
+/// Removes the cache file at `path`, if there is one.
+pub fn clear_station_cache(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Mutex,
+};
+
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::retry;
+
+static SKIPPED: AtomicUsize = AtomicUsize::new(0);
+static STRICT: AtomicBool = AtomicBool::new(false);
+static PROBLEMS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The number of malformed response elements skipped so far.
+pub fn skipped_elements() -> usize {
+    SKIPPED.load(Ordering::Relaxed)
+}
+
+/// In strict mode, every skipped element and every missing field that was
+/// filled with a default is recorded, see [`data_problems`].
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
+}
+
+/// The problems found in responses so far in strict mode, e.g.
+/// "Departure[3].platform: missing".
+pub fn data_problems() -> Vec<String> {
+    PROBLEMS.lock().unwrap().clone()
+}
+
+/// Fields the API sends under a misspelled key, declared with
+/// `#[serde(alias)]`, as pairs of the key a field is serialized as and the
+/// alias.
+const ALIASES: &[(&str, &str)] = &[("platformChanged", "platfromChanged")];
+
+/// Fetches a JSON array and decodes each element on its own, so a single
+/// malformed element is skipped instead of failing the whole response.
+pub(crate) async fn get_array<T: DeserializeOwned + Serialize>(
+    client: &Client,
+    url: &str,
+) -> Result<Vec<T>, reqwest::Error> {
+    let values = retry::get(client, url).await?.json::<Vec<Value>>().await?;
+    Ok(decode_array(&values))
+}
+
+/// Decodes `values` like [`decode_elements`], counting the skipped elements
+/// and, in strict mode, recording the problems found.
+fn decode_array<T: DeserializeOwned + Serialize>(values: &[Value]) -> Vec<T> {
+    let strict = STRICT.load(Ordering::Relaxed);
+    let (elements, problems) = decode_elements(values, strict);
+    SKIPPED.fetch_add(values.len() - elements.len(), Ordering::Relaxed);
+    if strict {
+        PROBLEMS.lock().unwrap().extend(problems);
+    }
+    elements
+}
+
+/// Decodes each element of `values` on its own. Returns the elements that
+/// could be decoded and the problems found: the elements that could not and,
+/// if `strict`, the fields that were filled with a default.
+fn decode_elements<T: DeserializeOwned + Serialize>(
+    values: &[Value],
+    strict: bool,
+) -> (Vec<T>, Vec<String>) {
+    let type_name = std::any::type_name::<T>().rsplit("::").next().unwrap();
+    let mut problems = Vec::new();
+    let mut elements = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        let path = format!("{}[{}]", type_name, i);
+        match T::deserialize(value) {
+            Ok(element) => {
+                if strict {
+                    if let Ok(decoded) = serde_json::to_value(&element) {
+                        missing_fields(value, &decoded, &path, &mut problems);
+                    }
+                }
+                elements.push(element);
+            }
+            Err(e) => problems.push(format!("{}: {}", path, e)),
+        }
+    }
+    (elements, problems)
+}
+
+/// Collects the fields of `decoded` that are absent from `original`, i.e.
+/// those that were filled with a default. Absent `Option` fields decode to
+/// `null` and are not reported, and fields are also looked up by their
+/// [`ALIASES`].
+fn missing_fields(original: &Value, decoded: &Value, path: &str, problems: &mut Vec<String>) {
+    match (original, decoded) {
+        (Value::Object(original), Value::Object(decoded)) => {
+            for (key, value) in decoded {
+                if value.is_null() {
+                    continue;
+                }
+                let path = format!("{}.{}", path, key);
+                let alias = ALIASES
+                    .iter()
+                    .find(|(name, _)| name == key)
+                    .and_then(|(_, alias)| original.get(*alias));
+                match original.get(key).or(alias) {
+                    Some(original) => missing_fields(original, value, &path, problems),
+                    None => problems.push(format!("{}: missing", path)),
+                }
+            }
+        }
+        (Value::Array(original), Value::Array(decoded)) => {
+            for (i, (original, value)) in original.iter().zip(decoded).enumerate() {
+                missing_fields(original, value, &format!("{}[{}]", path, i), problems);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{decode_array, decode_elements, skipped_elements};
+    use crate::departures::Departure;
+
+    fn fixture(json: &str) -> Vec<Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn complete_response_passes_strict_mode() {
+        let values = fixture(include_str!("../tests/fixtures/departures.json"));
+        let (departures, problems) = decode_elements::<Departure>(&values, true);
+        assert_eq!(departures.len(), 2);
+        assert_eq!(problems, Vec::<String>::new());
+    }
+
+    #[test]
+    fn strict_mode_reports_defaulted_fields() {
+        let mut values = fixture(include_str!("../tests/fixtures/departures.json"));
+        values[1].as_object_mut().unwrap().remove("platfromChanged");
+        values[1].as_object_mut().unwrap().remove("messages");
+        let (departures, problems) = decode_elements::<Departure>(&values, true);
+        assert_eq!(departures.len(), 2);
+        assert_eq!(
+            problems,
+            [
+                "Departure[1].messages: missing",
+                "Departure[1].platformChanged: missing"
+            ]
+        );
+    }
+
+    #[test]
+    fn corrupt_element_is_skipped() {
+        let mut values = fixture(include_str!("../tests/fixtures/departures.json"));
+        values[0]["plannedDepartureTime"] = Value::from("not a timestamp");
+        let skipped = skipped_elements();
+        let departures = decode_array::<Departure>(&values);
+        assert_eq!(departures.len(), 1);
+        assert_eq!(departures[0].label, "52");
+        assert!(skipped_elements() > skipped);
+    }
+}
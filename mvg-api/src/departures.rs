@@ -1,34 +1,132 @@
-use chrono::{DateTime, Local};
-use serde::Deserialize;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "chrono")]
 use serde_with::TimestampMilliSeconds;
 
-#[serde_with::serde_as]
-#[derive(Deserialize, Debug)]
+use crate::{
+    decode::get_array, error::MvgError, line::TransportType, occupancy::Occupancy,
+    platform::platform_display, time::TimestampMillis,
+};
+
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Departure {
-    #[serde_as(as = "TimestampMilliSeconds<i64>")]
-    pub planned_departure_time: DateTime<Local>,
+    #[cfg_attr(
+        feature = "chrono",
+        serde(with = "serde_with::As::<TimestampMilliSeconds<i64>>")
+    )]
+    pub planned_departure_time: TimestampMillis,
+    #[serde(default)]
     pub realtime: bool,
     pub delay_in_minutes: Option<isize>,
-    #[serde_as(as = "TimestampMilliSeconds<i64>")]
-    pub realtime_departure_time: DateTime<Local>,
-    pub transport_type: String,
+    #[cfg_attr(
+        feature = "chrono",
+        serde(with = "serde_with::As::<TimestampMilliSeconds<i64>>")
+    )]
+    pub realtime_departure_time: TimestampMillis,
+    pub transport_type: TransportType,
     pub label: String,
+    #[serde(default)]
     pub network: String,
+    #[serde(default)]
     pub train_type: String,
     pub destination: String,
+    #[serde(default)]
     pub cancelled: bool,
+    #[serde(default)]
     pub sev: bool,
     pub platform: Option<usize>,
+    #[serde(default, alias = "platfromChanged")]
+    pub platform_changed: bool,
     pub stop_position_number: Option<usize>,
+    #[serde(default)]
     pub messages: Vec<String>,
+    #[serde(default)]
     pub banner_hash: String,
-    pub occupancy: String,
+    #[serde(default)]
+    pub occupancy: Occupancy,
+    #[serde(default)]
     pub stop_point_global_id: String,
 }
 
-pub async fn get_departures(station_id: &str, offset_in_min: usize) -> Result<Vec<Departure>, reqwest::Error> {
-    let url = format!("https://www.mvg.de/api/fib/v2/departure?globalId={}&limit=10&offsetInMinutes={}&transportTypes=UBAHN,TRAM,BUS,SBAHN,SCHIFF", station_id, offset_in_min);
-    let resp = reqwest::get(url).await?.json::<Vec<Departure>>().await?;
-    Ok(resp)
+impl Departure {
+    /// The realtime departure time if available, the planned one otherwise.
+    pub fn effective_departure(&self) -> TimestampMillis {
+        if self.realtime {
+            self.realtime_departure_time
+        } else {
+            self.planned_departure_time
+        }
+    }
+
+    /// The platform, e.g. "Pl. 3" or "Pl. 3 (changed)".
+    pub fn platform_display(&self) -> Option<String> {
+        platform_display(self.platform, self.platform_changed)
+    }
+}
+
+/// Fetches at most `limit` departures, starting `offset_in_min` minutes from
+/// now. Fails with [`MvgError::ZeroLimit`] if `limit` is 0.
+pub async fn get_departures(
+    station_id: &str,
+    offset_in_min: usize,
+    limit: usize,
+) -> Result<Vec<Departure>, MvgError> {
+    get_departures_with_client(&Client::new(), station_id, offset_in_min, limit).await
+}
+
+/// Like [`get_departures`], but sends the request with `client`.
+pub async fn get_departures_with_client(
+    client: &Client,
+    station_id: &str,
+    offset_in_min: usize,
+    limit: usize,
+) -> Result<Vec<Departure>, MvgError> {
+    if limit == 0 {
+        return Err(MvgError::ZeroLimit);
+    }
+    Ok(get_array(client, &departures_url(station_id, offset_in_min, limit)).await?)
+}
+
+fn departures_url(station_id: &str, offset_in_min: usize, limit: usize) -> String {
+    format!("https://www.mvg.de/api/fib/v2/departure?globalId={}&limit={}&offsetInMinutes={}&transportTypes=UBAHN,TRAM,BUS,SBAHN,SCHIFF", station_id, limit, offset_in_min)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{departures_url, get_departures, Departure};
+    use crate::error::MvgError;
+    use crate::occupancy::Occupancy;
+
+    #[test]
+    fn omitted_fields_default() {
+        let mut values: Vec<Value> =
+            serde_json::from_str(include_str!("../tests/fixtures/departures.json")).unwrap();
+        let departure = values[0].as_object_mut().unwrap();
+        for field in ["network", "messages", "occupancy", "cancelled"] {
+            departure.remove(field);
+        }
+        let departure = serde_json::from_value::<Departure>(values.remove(0)).unwrap();
+        assert_eq!(departure.label, "U6");
+        assert_eq!(departure.network, "");
+        assert!(departure.messages.is_empty());
+        assert_eq!(departure.occupancy, Occupancy::Unknown);
+        assert!(!departure.cancelled);
+    }
+
+    #[test]
+    fn url_contains_offset_and_limit() {
+        let url = departures_url("de:09162:6", 30, 10);
+        assert!(url.contains("globalId=de:09162:6"));
+        assert!(url.contains("offsetInMinutes=30"));
+        assert!(url.contains("limit=10"));
+    }
+
+    #[tokio::test]
+    async fn zero_limit_is_rejected() {
+        let result = get_departures("de:09162:6", 0, 0).await;
+        assert!(matches!(result, Err(MvgError::ZeroLimit)));
+    }
 }
@@ -0,0 +1,40 @@
+//! The error of lookups that can find nothing, like
+//! [`get_station_detail`](crate::get_station_detail), or be given invalid
+//! arguments, like [`get_departures`](crate::get_departures).
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum MvgError {
+    /// The request failed or its response could not be decoded.
+    Request(reqwest::Error),
+    /// There is no station with this global id.
+    NoStation(String),
+    /// Departures were requested with a limit of 0.
+    ZeroLimit,
+}
+
+impl fmt::Display for MvgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MvgError::Request(e) => e.fmt(f),
+            MvgError::NoStation(id) => write!(f, "No station with the id {}", id),
+            MvgError::ZeroLimit => f.write_str("The limit of departures must be at least 1"),
+        }
+    }
+}
+
+impl std::error::Error for MvgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MvgError::Request(e) => Some(e),
+            MvgError::NoStation(_) | MvgError::ZeroLimit => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for MvgError {
+    fn from(error: reqwest::Error) -> Self {
+        MvgError::Request(error)
+    }
+}
@@ -1,10 +1,43 @@
+//! Client for the APIs of the Münchner Verkehrsgesellschaft (MVG).
+//!
+//! # Features
+//!
+//! | Feature         | Default | Description                                                  |
+//! |-----------------|---------|--------------------------------------------------------------|
+//! | `chrono`        | yes     | Parse timestamps into `chrono::DateTime<Local>` (pulls in `chrono` and `serde_with`) |
+//! | `station-cache` | no      | Cache station lookups on disk, see `cache`                   |
+//!
+//! Without `chrono`, timestamps stay raw, see [`time`].
+
+#[cfg(feature = "station-cache")]
+pub mod cache;
+mod decode;
 pub mod departures;
+pub mod error;
+pub mod line;
 pub mod notifications;
+pub mod occupancy;
+pub mod platform;
+pub mod polyline;
+mod retry;
 pub mod routes;
 pub mod station;
+pub mod time;
 
-pub use departures::get_departures;
-pub use notifications::get_notifications;
-pub use routes::get_routes;
-pub use station::get_station;
+pub use decode::{data_problems, set_strict, skipped_elements};
+pub use departures::{get_departures, get_departures_with_client};
+pub use error::MvgError;
+pub use line::{LineCategory, TransportType};
+pub use notifications::{get_notifications, get_notifications_with_client};
+pub use occupancy::Occupancy;
+pub use polyline::decode_polyline;
+pub use reqwest::{Client, Error};
+pub use routes::GetRoutesConfig;
+pub use routes::RoutePoint;
+pub use routes::{
+    get_routes, get_routes_with_client, get_station_detail, get_station_detail_with_client,
+};
 pub use station::Location;
+pub use station::{
+    get_station, get_station_by_id, get_station_by_id_with_client, get_station_with_client,
+};
@@ -0,0 +1,171 @@
+//! Classification of lines by their label and transport type, shared by
+//! [`routes::Line`](crate::routes::Line) and
+//! [`notifications::NotificationLines`](crate::notifications::NotificationLines).
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The transport type of a line as named by the API, e.g. `"UBAHN"`. Types
+/// this crate does not know are kept in [`TransportType::Other`].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum TransportType {
+    Ubahn,
+    Sbahn,
+    Tram,
+    Bus,
+    RegionalBus,
+    /// Ferries on the lakes around Munich (`SCHIFF`)
+    Ferry,
+    /// Call-a-cab services (`RUFTAXI`)
+    Taxi,
+    /// Walking legs of a connection (`PEDESTRIAN`)
+    Pedestrian,
+    Other(String),
+}
+
+impl TransportType {
+    /// The name of the type in the API, e.g. `"UBAHN"`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            TransportType::Ubahn => "UBAHN",
+            TransportType::Sbahn => "SBAHN",
+            TransportType::Tram => "TRAM",
+            TransportType::Bus => "BUS",
+            TransportType::RegionalBus => "REGIONAL_BUS",
+            TransportType::Ferry => "SCHIFF",
+            TransportType::Taxi => "RUFTAXI",
+            TransportType::Pedestrian => "PEDESTRIAN",
+            TransportType::Other(name) => name,
+        }
+    }
+}
+
+impl Default for TransportType {
+    /// An unknown, empty type, as for responses without one.
+    fn default() -> Self {
+        TransportType::Other(String::new())
+    }
+}
+
+impl From<String> for TransportType {
+    fn from(name: String) -> Self {
+        match name.to_uppercase().as_str() {
+            "UBAHN" => TransportType::Ubahn,
+            "SBAHN" => TransportType::Sbahn,
+            "TRAM" => TransportType::Tram,
+            "BUS" => TransportType::Bus,
+            "REGIONAL_BUS" => TransportType::RegionalBus,
+            "SCHIFF" => TransportType::Ferry,
+            "RUFTAXI" => TransportType::Taxi,
+            "PEDESTRIAN" => TransportType::Pedestrian,
+            _ => TransportType::Other(name),
+        }
+    }
+}
+
+impl From<TransportType> for String {
+    fn from(transport_type: TransportType) -> Self {
+        match transport_type {
+            TransportType::Other(name) => name,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TransportType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The kind of service a line belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCategory {
+    Ubahn,
+    Sbahn,
+    Tram,
+    Bus,
+    RegionalBus,
+    Ferry,
+    /// A walking leg of a connection, see [`TransportType::Pedestrian`].
+    Walk,
+    Other,
+}
+
+impl LineCategory {
+    /// Classifies a line by its transport type. If the type is unknown or
+    /// empty, U-Bahn and S-Bahn lines are still recognized by their label,
+    /// e.g. `"U6"` or `"S20"`.
+    pub fn classify(label: &str, transport_type: &TransportType) -> Self {
+        match transport_type {
+            TransportType::Ubahn => LineCategory::Ubahn,
+            TransportType::Sbahn => LineCategory::Sbahn,
+            TransportType::Tram => LineCategory::Tram,
+            TransportType::Bus => LineCategory::Bus,
+            TransportType::RegionalBus => LineCategory::RegionalBus,
+            TransportType::Ferry => LineCategory::Ferry,
+            TransportType::Pedestrian => LineCategory::Walk,
+            _ if has_prefix(label, 'U') => LineCategory::Ubahn,
+            _ if has_prefix(label, 'S') => LineCategory::Sbahn,
+            _ => LineCategory::Other,
+        }
+    }
+}
+
+/// Whether the label belongs to a night line, e.g. `"N40"` or `"N19"`.
+pub fn is_night_line(label: &str) -> bool {
+    has_prefix(label, 'N')
+}
+
+/// Whether the label belongs to an express line, i.e. an express bus like
+/// `"X30"` or an express variant like `"S2X"`.
+pub fn is_express(label: &str) -> bool {
+    has_prefix(label, 'X') || (label.len() > 1 && label.ends_with('X'))
+}
+
+/// Whether `label` is `prefix` followed by a line number.
+fn has_prefix(label: &str, prefix: char) -> bool {
+    let mut chars = label.chars();
+    chars.next() == Some(prefix) && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_express, is_night_line, LineCategory, TransportType};
+
+    fn classify(label: &str, transport_type: &str) -> LineCategory {
+        LineCategory::classify(label, &TransportType::from(transport_type.to_string()))
+    }
+
+    #[test]
+    fn classifies_by_transport_type() {
+        assert_eq!(classify("U6", "UBAHN"), LineCategory::Ubahn);
+        assert_eq!(classify("S20", "SBAHN"), LineCategory::Sbahn);
+        assert_eq!(classify("12", "TRAM"), LineCategory::Tram);
+        assert_eq!(classify("X30", "BUS"), LineCategory::Bus);
+        assert_eq!(classify("N40", "BUS"), LineCategory::Bus);
+        assert_eq!(classify("210", "REGIONAL_BUS"), LineCategory::RegionalBus);
+        assert_eq!(classify("Fußweg", "PEDESTRIAN"), LineCategory::Walk);
+    }
+
+    #[test]
+    fn classifies_by_label_without_transport_type() {
+        assert_eq!(classify("U6", ""), LineCategory::Ubahn);
+        assert_eq!(classify("S20", ""), LineCategory::Sbahn);
+        assert_eq!(classify("12", ""), LineCategory::Other);
+        assert_eq!(classify("Seehausen", ""), LineCategory::Other);
+    }
+
+    #[test]
+    fn recognizes_night_and_express_lines() {
+        assert!(is_night_line("N40"));
+        assert!(is_night_line("N19"));
+        assert!(!is_night_line("52"));
+        assert!(is_express("X30"));
+        assert!(is_express("S2X"));
+        assert!(!is_express("U6"));
+        assert!(!is_express("X"));
+    }
+}
@@ -1,56 +1,111 @@
-use chrono::{DateTime, Local};
-use serde::Deserialize;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug)]
+use crate::{
+    decode::get_array,
+    line::{self, LineCategory, TransportType},
+    time::Timestamp,
+};
+
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Notification {
     pub id: String,
     #[serde(rename = "type")]
+    #[serde(default)]
     pub type_name: String,
     pub title: String,
+    #[serde(default)]
     pub text: String,
+    #[serde(default)]
     pub html_text: String,
+    #[serde(default)]
     pub lines: Vec<NotificationLines>,
+    #[serde(default)]
     pub incidents: Vec<String>,
+    #[serde(default)]
     pub links: Vec<NotificationLink>,
+    #[serde(default)]
     pub download_links: Vec<DownloadLink>,
+    #[serde(default)]
     pub incident_duration: Vec<Duration>,
     pub active_duration: Duration,
+    #[serde(default)]
     pub modification_date: String,
+    #[serde(default)]
+    pub provider: String,
+    #[serde(default)]
+    pub event_types: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[cfg(feature = "chrono")]
+impl Notification {
+    /// Whether the notification is in effect at `now`.
+    pub fn is_active(&self, now: chrono::DateTime<chrono::Local>) -> bool {
+        self.active_duration.from_date <= now
+            && self.active_duration.to_date.is_none_or(|to| to >= now)
+    }
+
+    /// Whether the notification only takes effect after `now`.
+    pub fn is_upcoming(&self, now: chrono::DateTime<chrono::Local>) -> bool {
+        self.active_duration.from_date > now
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct NotificationLines {
     pub id: String,
     pub name: String,
-    pub type_of_transport: String,
+    #[serde(default)]
+    pub type_of_transport: TransportType,
+    #[serde(default)]
     pub stations: Vec<NotificationStation>,
+    #[serde(default)]
     pub direction: String,
+    #[serde(default)]
+    pub sev: bool,
+}
+
+impl NotificationLines {
+    /// Whether this is a night line, e.g. N40.
+    pub fn is_night_line(&self) -> bool {
+        line::is_night_line(&self.name)
+    }
+
+    /// Whether this is an express line, e.g. X30.
+    pub fn is_express(&self) -> bool {
+        line::is_express(&self.name)
+    }
+
+    /// The kind of service this line belongs to.
+    pub fn category(&self) -> LineCategory {
+        LineCategory::classify(&self.name, &self.type_of_transport)
+    }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct NotificationStation {
     pub id: String,
     pub name: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Duration {
-    pub from_date: DateTime<Local>,
-    pub to_date: Option<DateTime<Local>>,
+    pub from_date: Timestamp,
+    pub to_date: Option<Timestamp>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct NotificationLink {
     pub href: String,
     pub name: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct DownloadLink {
     pub id: String,
@@ -59,7 +114,13 @@ pub struct DownloadLink {
 }
 
 pub async fn get_notifications() -> Result<Vec<Notification>, reqwest::Error> {
+    get_notifications_with_client(&Client::new()).await
+}
+
+/// Like [`get_notifications`], but sends the request with `client`.
+pub async fn get_notifications_with_client(
+    client: &Client,
+) -> Result<Vec<Notification>, reqwest::Error> {
     let url = "https://www.mvg.de/api/ems/tickers".to_string();
-    let resp = reqwest::get(url).await?.json::<Vec<Notification>>().await?;
-    Ok(resp)
+    get_array(client, &url).await
 }
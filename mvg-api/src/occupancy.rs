@@ -0,0 +1,98 @@
+//! The expected occupancy of vehicles, shared by
+//! [`routes::ConnectionPart`](crate::routes::ConnectionPart),
+//! [`routes::Station`](crate::routes::Station) and
+//! [`departures::Departure`](crate::departures::Departure).
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// How full a vehicle is expected to be, as `"LOW"`, `"MEDIUM"` or `"HIGH"`
+/// in the API. Any other value is [`Occupancy::Unknown`], which sorts before
+/// [`Occupancy::Low`], so filtering by a maximum keeps vehicles without data.
+#[derive(
+    Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+#[serde(from = "String", into = "String")]
+pub enum Occupancy {
+    #[default]
+    Unknown,
+    Low,
+    Medium,
+    High,
+}
+
+impl Occupancy {
+    /// The name of the occupancy in the API, e.g. `"LOW"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Occupancy::Unknown => "UNKNOWN",
+            Occupancy::Low => "LOW",
+            Occupancy::Medium => "MEDIUM",
+            Occupancy::High => "HIGH",
+        }
+    }
+}
+
+impl From<&str> for Occupancy {
+    fn from(name: &str) -> Self {
+        match name.to_uppercase().as_str() {
+            "LOW" => Occupancy::Low,
+            "MEDIUM" => Occupancy::Medium,
+            "HIGH" => Occupancy::High,
+            _ => Occupancy::Unknown,
+        }
+    }
+}
+
+impl From<String> for Occupancy {
+    fn from(name: String) -> Self {
+        Occupancy::from(name.as_str())
+    }
+}
+
+impl From<Occupancy> for String {
+    fn from(occupancy: Occupancy) -> Self {
+        occupancy.as_str().to_string()
+    }
+}
+
+impl fmt::Display for Occupancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Occupancy;
+
+    #[test]
+    fn parses_known_values() {
+        assert_eq!(Occupancy::from("LOW"), Occupancy::Low);
+        assert_eq!(Occupancy::from("MEDIUM"), Occupancy::Medium);
+        assert_eq!(Occupancy::from("HIGH"), Occupancy::High);
+        assert_eq!(Occupancy::from("medium"), Occupancy::Medium);
+    }
+
+    #[test]
+    fn parses_other_values_as_unknown() {
+        assert_eq!(Occupancy::from("UNKNOWN"), Occupancy::Unknown);
+        assert_eq!(Occupancy::from("FULL"), Occupancy::Unknown);
+        assert_eq!(Occupancy::from(""), Occupancy::Unknown);
+    }
+
+    #[test]
+    fn orders_unknown_below_low() {
+        assert!(Occupancy::Unknown < Occupancy::Low);
+        assert!(Occupancy::Low < Occupancy::Medium);
+        assert!(Occupancy::Medium < Occupancy::High);
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let occupancy: Occupancy = serde_json::from_str("\"HIGH\"").unwrap();
+        assert_eq!(occupancy, Occupancy::High);
+        assert_eq!(serde_json::to_string(&occupancy).unwrap(), "\"HIGH\"");
+    }
+}
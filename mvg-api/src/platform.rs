@@ -0,0 +1,14 @@
+//! Formatting of platforms, shared by
+//! [`routes::Station`](crate::routes::Station) and
+//! [`departures::Departure`](crate::departures::Departure).
+
+/// Formats a platform like "Pl. 3", or "Pl. 3 (changed)" if it differs from
+/// the planned one.
+pub fn platform_display(platform: Option<usize>, changed: bool) -> Option<String> {
+    let platform = platform?;
+    Some(if changed {
+        format!("Pl. {} (changed)", platform)
+    } else {
+        format!("Pl. {}", platform)
+    })
+}
@@ -0,0 +1,101 @@
+const EARTH_RADIUS_IN_METERS: f64 = 6_371_000.0;
+
+/// Decodes a Google encoded polyline with a precision of 1e5 into
+/// `(latitude, longitude)` pairs. Returns `None` if the string is malformed.
+pub fn decode_polyline(encoded: &str) -> Option<Vec<(f64, f64)>> {
+    let mut bytes = encoded.bytes();
+    let mut coordinates = Vec::new();
+    let (mut latitude, mut longitude) = (0i64, 0i64);
+
+    while let Some(delta_latitude) = decode_value(&mut bytes, true)? {
+        let delta_longitude = decode_value(&mut bytes, false)??;
+        latitude += delta_latitude;
+        longitude += delta_longitude;
+        coordinates.push((latitude as f64 / 1e5, longitude as f64 / 1e5));
+    }
+
+    Some(coordinates)
+}
+
+/// Decodes the next value. The outer `None` marks malformed input, the inner
+/// `None` the end of the input, which is only valid where `may_end` is set.
+fn decode_value(bytes: &mut impl Iterator<Item = u8>, may_end: bool) -> Option<Option<i64>> {
+    let mut result = 0i64;
+    let mut shift = 0;
+    loop {
+        let byte = match bytes.next() {
+            Some(b) => b,
+            None if shift == 0 && may_end => return Some(None),
+            None => return None,
+        };
+        if !(63..=126).contains(&byte) || shift > 60 {
+            return None;
+        }
+        let chunk = (byte - 63) as i64;
+        result |= (chunk & 0x1f) << shift;
+        shift += 5;
+        if chunk < 0x20 {
+            break;
+        }
+    }
+    let value = if result & 1 == 1 {
+        !(result >> 1)
+    } else {
+        result >> 1
+    };
+    Some(Some(value))
+}
+
+/// The length of a path in meters, using the haversine formula.
+pub fn path_length(coordinates: &[(f64, f64)]) -> f64 {
+    coordinates
+        .windows(2)
+        .map(|pair| {
+            let ((lat1, lon1), (lat2, lon2)) = (pair[0], pair[1]);
+            let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+            let delta_lat = lat2 - lat1;
+            let delta_lon = (lon2 - lon1).to_radians();
+            let a = (delta_lat / 2.0).sin().powi(2)
+                + lat1.cos() * lat2.cos() * (delta_lon / 2.0).sin().powi(2);
+            2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_polyline;
+
+    fn assert_coordinates(actual: &[(f64, f64)], expected: &[(f64, f64)]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a.0 - e.0).abs() < 1e-9 && (a.1 - e.1).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn decodes_google_reference_polyline() {
+        let coordinates = decode_polyline("_p~iF~ps|U_ulLnnqC_mqNvxq`@").unwrap();
+        assert_coordinates(
+            &coordinates,
+            &[(38.5, -120.2), (40.7, -120.95), (43.252, -126.453)],
+        );
+    }
+
+    #[test]
+    fn decodes_empty_polyline() {
+        assert_eq!(decode_polyline(""), Some(Vec::new()));
+    }
+
+    #[test]
+    fn decodes_path_in_munich() {
+        let coordinates = decode_polyline("yxxdHkyseAnVlv@").unwrap();
+        assert_coordinates(&coordinates, &[(48.13725, 11.57542), (48.13349, 11.56655)]);
+    }
+
+    #[test]
+    fn rejects_truncated_polyline() {
+        // A latitude without its longitude.
+        assert_eq!(decode_polyline("_p~iF"), None);
+    }
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use reqwest::{Client, Response, StatusCode};
+
+/// How often a request is retried after a transient failure.
+const RETRIES: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Sends a GET request, retrying connection failures and 502, 503 and 504
+/// responses with exponential backoff. Timeouts are not retried, so that a
+/// request gives up after the client's timeout, and other error statuses are
+/// returned as errors right away.
+pub(crate) async fn get(client: &Client, url: &str) -> Result<Response, reqwest::Error> {
+    let mut backoff = INITIAL_BACKOFF;
+    for _ in 0..RETRIES {
+        match send(client, url).await {
+            Err(e) if is_transient(&e) => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    send(client, url).await
+}
+
+async fn send(client: &Client, url: &str) -> Result<Response, reqwest::Error> {
+    client.get(url).send().await?.error_for_status()
+}
+
+fn is_transient(error: &reqwest::Error) -> bool {
+    (error.is_connect() && !error.is_timeout())
+        || matches!(
+            error.status(),
+            Some(
+                StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            )
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use reqwest::{Client, StatusCode};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::get;
+
+    /// Serves one response per connection with the given statuses in turn,
+    /// repeating the last one. Returns the URL and the number of requests.
+    async fn serve(statuses: &'static [u16]) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 1024];
+                let _ = stream.read(&mut buffer).await;
+                let i = counter.fetch_add(1, Ordering::SeqCst);
+                let status = statuses[i.min(statuses.len() - 1)];
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-length: 2\r\nconnection: close\r\n\r\n[]",
+                    status
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let (url, requests) = serve(&[503, 502, 200]).await;
+        let response = get(&Client::new(), &url).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let (url, requests) = serve(&[404, 200]).await;
+        let error = get(&Client::new(), &url).await.unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_timeouts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                streams.push(stream);
+            }
+        });
+        let client = Client::builder()
+            .timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let error = get(&client, &url).await.unwrap_err();
+        assert!(error.is_timeout());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
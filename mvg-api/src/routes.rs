@@ -1,76 +1,245 @@
-use chrono::{DateTime, Local, SecondsFormat, Utc};
-use serde::Deserialize;
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug)]
+use crate::{
+    decode::get_array,
+    error::MvgError,
+    line::{self, LineCategory, TransportType},
+    occupancy::Occupancy,
+    platform::platform_display,
+    polyline::{decode_polyline, path_length},
+    station::StationDetail,
+    time::Timestamp,
+};
+
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Connection {
     pub unique_id: isize,
     pub parts: Vec<ConnectionPart>,
+    #[serde(default)]
     pub ticketing_information: TicketingInformation,
 }
 
-#[derive(Deserialize, Debug)]
+impl Connection {
+    /// The station where the first leg starts.
+    pub fn origin(&self) -> &Station {
+        &self.parts[0].from
+    }
+
+    /// The station where the last leg ends.
+    pub fn destination(&self) -> &Station {
+        &self.parts[self.parts.len() - 1].to
+    }
+
+    /// The number of changes between legs.
+    pub fn num_changes(&self) -> usize {
+        self.parts.len().saturating_sub(1)
+    }
+
+    /// The names of the origin, destination and interchange stations with an
+    /// out-of-order elevator, each listed once.
+    pub fn stations_with_broken_elevator(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for station in self.parts.iter().flat_map(|part| [&part.from, &part.to]) {
+            if station.has_out_of_order_elevator && !names.contains(&station.name.as_str()) {
+                names.push(&station.name);
+            }
+        }
+        names
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Connection {
+    /// The planned time from departure at the origin to arrival at the destination.
+    pub fn total_duration(&self) -> chrono::Duration {
+        self.destination().planned_departure - self.origin().planned_departure
+    }
+
+    /// Whether a realtime delay threatens a transfer, i.e. a leg arrives so
+    /// late that the time until the next leg's (possibly delayed) departure
+    /// is used up. Walks are not transfers of their own, their duration is
+    /// taken from the time between the legs they connect.
+    pub fn has_transfer_at_risk(&self) -> bool {
+        let mut arriving: Option<&ConnectionPart> = None;
+        let mut walk = chrono::Duration::zero();
+        for part in &self.parts {
+            if part.is_walk() {
+                walk = walk + (part.to.planned_departure - part.from.planned_departure);
+                continue;
+            }
+            if let Some(arriving) = arriving {
+                let delay = arriving
+                    .to
+                    .arrival_delay_in_minutes
+                    .or(arriving.from.departure_delay_in_minutes)
+                    .unwrap_or(0);
+                let next_delay = part.from.departure_delay_in_minutes.unwrap_or(0);
+                let buffer = (part.from.planned_departure - arriving.to.planned_departure - walk)
+                    .num_minutes() as isize;
+                if delay > 0 && delay - next_delay >= buffer {
+                    return true;
+                }
+            }
+            arriving = Some(part);
+            walk = chrono::Duration::zero();
+        }
+        false
+    }
+
+    /// Whether the first leg has already left the origin at `now`, taking
+    /// its realtime delay into account.
+    pub fn has_departed(&self, now: DateTime<chrono::Local>) -> bool {
+        let origin = self.origin();
+        let delay = origin.departure_delay_in_minutes.unwrap_or(0) as i64;
+        origin.planned_departure + chrono::Duration::minutes(delay) < now
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ConnectionPart {
     pub from: Station,
     pub to: Station,
+    #[serde(default)]
     pub intermediate_stops: Vec<Station>,
+    #[serde(default)]
     pub no_change_required: bool,
     pub line: Line,
+    #[serde(default)]
     pub path_polyline: String,
+    #[serde(default)]
     pub interchange_path_polyline: String,
+    #[serde(default)]
     pub path_description: Vec<PathDescription>,
+    #[serde(default)]
     pub exit_letter: String,
+    #[serde(default)]
     pub distance: f64,
-    pub occupancy: String,
+    #[serde(default)]
+    pub occupancy: Occupancy,
+    #[serde(default)]
     pub messages: Vec<String>,
+    #[serde(default)]
+    pub infos: Vec<String>,
+}
+
+impl ConnectionPart {
+    /// The `(latitude, longitude)` pairs of the leg's path. Empty if there is
+    /// no path or it cannot be decoded.
+    pub fn coordinates(&self) -> Vec<(f64, f64)> {
+        decode_polyline(&self.path_polyline).unwrap_or_default()
+    }
+
+    /// The `(latitude, longitude)` pairs of the walking path of a change
+    /// after this leg, if any.
+    pub fn interchange_coordinates(&self) -> Vec<(f64, f64)> {
+        decode_polyline(&self.interchange_path_polyline).unwrap_or_default()
+    }
+
+    /// Whether the leg is walked rather than travelled on a line.
+    pub fn is_walk(&self) -> bool {
+        self.line.category() == LineCategory::Walk
+    }
+
+    /// The length of the leg in meters, measured along its decoded path.
+    /// Falls back to `distance` if the path cannot be decoded.
+    pub fn path_length(&self) -> f64 {
+        match self.coordinates() {
+            coordinates if coordinates.len() > 1 => path_length(&coordinates),
+            _ => self.distance,
+        }
+    }
 }
 
 // #[serde_with::serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Station {
     pub latitude: f64,
     pub longitude: f64,
     pub station_global_id: String,
+    #[serde(default)]
     pub station_diva_id: usize,
     pub platform: Option<usize>,
+    #[serde(default, alias = "platfromChanged")]
+    pub platform_changed: bool,
+    #[serde(default)]
     pub place: String,
     pub name: String,
     // #[serde_as(as = "Rfc3339")]
-    pub planned_departure: DateTime<Local>,
+    pub planned_departure: Timestamp,
     pub departure_delay_in_minutes: Option<isize>,
     pub arrival_delay_in_minutes: Option<isize>,
+    #[serde(default)]
     pub transport_types: Vec<String>,
+    #[serde(default)]
     pub surrounding_plan_link: String,
-    pub occupancy: String,
+    #[serde(default)]
+    pub occupancy: Occupancy,
+    #[serde(default)]
     pub has_zoom_data: bool,
+    #[serde(default)]
     pub has_out_of_order_escalator: bool,
+    #[serde(default)]
     pub has_out_of_order_elevator: bool,
+    #[serde(default)]
+    pub is_via_stop: bool,
+}
+
+impl Station {
+    /// The platform, e.g. "Pl. 3" or "Pl. 3 (changed)".
+    pub fn platform_display(&self) -> Option<String> {
+        platform_display(self.platform, self.platform_changed)
+    }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Line {
     pub label: String,
-    pub transport_type: String,
+    pub transport_type: TransportType,
     pub destination: String,
+    #[serde(default)]
     pub train_type: String,
+    #[serde(default)]
     pub network: String,
+    #[serde(default)]
     pub sev: bool,
 }
 
-#[derive(Deserialize, Debug)]
+impl Line {
+    /// Whether this is a night line, e.g. N40.
+    pub fn is_night_line(&self) -> bool {
+        line::is_night_line(&self.label)
+    }
+
+    /// Whether this is an express line, e.g. X30.
+    pub fn is_express(&self) -> bool {
+        line::is_express(&self.label)
+    }
+
+    /// The kind of service this line belongs to.
+    pub fn category(&self) -> LineCategory {
+        LineCategory::classify(&self.label, &self.transport_type)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct TicketingInformation {
     pub zones: Vec<usize>,
     pub alternative_zones: Vec<usize>,
     pub unified_ticket_ids: Vec<String>,
+    #[serde(default)]
     pub distance: Option<f64>,
     pub banner_hash: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct PathDescription {
     pub from_path_coord_idx: isize,
@@ -78,59 +247,307 @@ pub struct PathDescription {
     pub level: isize,
 }
 
+pub enum RoutePoint {
+    Station(String),
+    Coordinates { latitude: f64, longitude: f64 },
+}
+
+impl RoutePoint {
+    fn to_query(&self, prefix: &str) -> String {
+        match self {
+            RoutePoint::Station(id) => format!("{}StationGlobalId={}", prefix, id),
+            RoutePoint::Coordinates {
+                latitude,
+                longitude,
+            } => format!(
+                "{0}Latitude={1}&{0}Longitude={2}",
+                prefix, latitude, longitude
+            ),
+        }
+    }
+}
+
+/// Which transport types `get_routes` may use. All are included by default,
+/// e.g. `GetRoutesConfig { include_bus: false, ..Default::default() }`
+/// avoids buses.
+#[derive(Debug, Clone)]
+pub struct GetRoutesConfig {
+    pub include_ubahn: bool,
+    pub include_bus: bool,
+    pub include_tram: bool,
+    pub include_sbahn: bool,
+    /// Call-a-cab services (`RUFTAXI`)
+    pub include_taxi: bool,
+    /// Regional buses of the MVV (`REGIONAL_BUS`)
+    pub include_regional_bus: bool,
+    /// Ferries on the lakes around Munich (`SCHIFF`)
+    pub include_ferry: bool,
+}
+
+impl Default for GetRoutesConfig {
+    fn default() -> Self {
+        GetRoutesConfig {
+            include_ubahn: true,
+            include_bus: true,
+            include_tram: true,
+            include_sbahn: true,
+            include_taxi: true,
+            include_regional_bus: true,
+            include_ferry: true,
+        }
+    }
+}
+
+impl GetRoutesConfig {
+    fn transport_types(&self) -> Vec<TransportType> {
+        [
+            (self.include_ubahn, TransportType::Ubahn),
+            (self.include_bus, TransportType::Bus),
+            (self.include_tram, TransportType::Tram),
+            (self.include_sbahn, TransportType::Sbahn),
+            (self.include_taxi, TransportType::Taxi),
+            (self.include_regional_bus, TransportType::RegionalBus),
+            (self.include_ferry, TransportType::Ferry),
+        ]
+        .into_iter()
+        .filter_map(|(included, transport_type)| included.then_some(transport_type))
+        .collect()
+    }
+}
+
 pub async fn get_routes(
-    from_station_id: &str,
-    to_station_id: &str,
-    time: Option<DateTime<Local>>,
+    from: &RoutePoint,
+    to: &RoutePoint,
+    time: Option<Timestamp>,
     arrival: Option<bool>,
-    include_ubahn: Option<bool>,
-    include_bus: Option<bool>,
-    include_tram: Option<bool>,
-    include_sbahn: Option<bool>,
-    include_taxi: Option<bool>,
+    config: &GetRoutesConfig,
 ) -> Result<Vec<Connection>, reqwest::Error> {
-    let mut transport_types = Vec::new();
+    get_routes_with_client(&Client::new(), from, to, time, arrival, config).await
+}
 
-    if let Some(x) = include_ubahn {
-        if x {
-            transport_types.push("UBAHN")
-        }
+/// Like [`get_routes`], but sends the request with `client`.
+pub async fn get_routes_with_client(
+    client: &Client,
+    from: &RoutePoint,
+    to: &RoutePoint,
+    time: Option<Timestamp>,
+    arrival: Option<bool>,
+    config: &GetRoutesConfig,
+) -> Result<Vec<Connection>, reqwest::Error> {
+    get_array(client, &routes_url(from, to, time, arrival, config)).await
+}
+
+fn routes_url(
+    from: &RoutePoint,
+    to: &RoutePoint,
+    time: Option<Timestamp>,
+    arrival: Option<bool>,
+    config: &GetRoutesConfig,
+) -> String {
+    let transport_types = config.transport_types();
+
+    #[cfg(feature = "chrono")]
+    let time = {
+        let time: DateTime<Utc> = match time {
+            Some(t) => DateTime::from(t),
+            None => Utc::now(),
+        };
+        Some(time.to_rfc3339_opts(SecondsFormat::Millis, true))
+    };
+    let routing_date_time = time
+        .map(|t| format!("&routingDateTime={}", t))
+        .unwrap_or_default();
+
+    format!(
+        "https://www.mvg.de/api/fib/v2/connection?{}&{}{}&routingDateTimeIsArrival={}&transportTypes={}",
+        from.to_query("origin"),
+        to.to_query("destination"),
+        routing_date_time,
+        arrival.unwrap_or(false),
+        transport_types
+            .iter()
+            .map(TransportType::as_str)
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
+/// Destinations of the route that [`get_station_detail`] fetches, Marienplatz
+/// and Hauptbahnhof.
+const DETAIL_ROUTE_DESTINATIONS: [&str; 2] = ["de:09162:2", "de:09162:6"];
+
+/// Fetches the details of the station with the given global id, e.g.
+/// `"de:09162:6"`. The API has no endpoint for the details of a station, so
+/// they are taken from the first stop of a route starting there, which costs
+/// a routing request. Fails with [`MvgError::NoStation`] if no route starts
+/// at the station.
+pub async fn get_station_detail(global_id: &str) -> Result<StationDetail, MvgError> {
+    get_station_detail_with_client(&Client::new(), global_id).await
+}
+
+/// Like [`get_station_detail`], but sends the request with `client`.
+pub async fn get_station_detail_with_client(
+    client: &Client,
+    global_id: &str,
+) -> Result<StationDetail, MvgError> {
+    let destination = DETAIL_ROUTE_DESTINATIONS
+        .into_iter()
+        .find(|id| *id != global_id)
+        .unwrap();
+    let routes = get_routes_with_client(
+        client,
+        &RoutePoint::Station(global_id.to_string()),
+        &RoutePoint::Station(destination.to_string()),
+        None,
+        None,
+        &GetRoutesConfig::default(),
+    )
+    .await?;
+    station_detail(&routes, global_id).ok_or_else(|| MvgError::NoStation(global_id.to_string()))
+}
+
+/// The details of the station with the given global id from the first stop
+/// of `routes` at that station or one of its platforms, whose ids are the
+/// station's id followed by e.g. `":52:52"`.
+fn station_detail(routes: &[Connection], global_id: &str) -> Option<StationDetail> {
+    let platform_prefix = format!("{}:", global_id);
+    let stop = routes
+        .iter()
+        .flat_map(|connection| &connection.parts)
+        .map(|part| &part.from)
+        .find(|stop| {
+            stop.station_global_id == global_id
+                || stop.station_global_id.starts_with(&platform_prefix)
+        })?;
+    Some(StationDetail {
+        global_id: global_id.to_string(),
+        name: stop.name.clone(),
+        place: stop.place.clone(),
+        latitude: stop.latitude,
+        longitude: stop.longitude,
+        transport_types: stop.transport_types.clone(),
+        has_out_of_order_elevator: stop.has_out_of_order_elevator,
+        has_out_of_order_escalator: stop.has_out_of_order_escalator,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "chrono")]
+    use super::{routes_url, GetRoutesConfig, RoutePoint};
+    use super::{station_detail, Connection};
+
+    fn fixture() -> Connection {
+        serde_json::from_str(include_str!("../tests/fixtures/connection.json")).unwrap()
     }
-    if let Some(x) = include_bus {
-        if x {
-            transport_types.push("BUS")
+
+    /// The fixture, a ride followed by a walk, with the first ride arriving
+    /// `delay` minutes late and, if `then_ride` is given, another ride
+    /// departing at that time after the walk, which ends at 00:09.
+    #[cfg(feature = "chrono")]
+    fn delayed_fixture(delay: isize, then_ride: Option<&str>) -> Connection {
+        let mut connection: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/fixtures/connection.json")).unwrap();
+        connection["parts"][0]["to"]["arrivalDelayInMinutes"] = delay.into();
+        if let Some(departure) = then_ride {
+            let mut ride = connection["parts"][0].clone();
+            ride["from"]["plannedDeparture"] = format!("2026-10-17T{}:00+02:00", departure).into();
+            ride["to"]["plannedDeparture"] = "2026-10-17T00:30:00+02:00".into();
+            ride["to"]["arrivalDelayInMinutes"] = 0.into();
+            connection["parts"].as_array_mut().unwrap().push(ride);
         }
+        serde_json::from_value(connection).unwrap()
     }
-    if let Some(x) = include_tram {
-        if x {
-            transport_types.push("TRAM")
-        }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn walk_to_destination_is_no_transfer() {
+        assert!(!delayed_fixture(1, None).has_transfer_at_risk());
     }
-    if let Some(x) = include_sbahn {
-        if x {
-            transport_types.push("SBAHN")
-        }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn walk_then_ride_with_enough_buffer() {
+        // The walk ends at 00:09 and the next ride leaves at 00:12.
+        assert!(!delayed_fixture(2, Some("00:12")).has_transfer_at_risk());
     }
-    if let Some(x) = include_taxi {
-        if x {
-            transport_types.push("RUFTAXI")
-        }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn walk_then_ride_with_delay_using_up_buffer() {
+        assert!(delayed_fixture(3, Some("00:12")).has_transfer_at_risk());
     }
 
-    let time: DateTime<Utc> = match time {
-        Some(t) => DateTime::from(t),
-        None => Utc::now(),
-    };
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn tight_transfer_without_walk() {
+        // The first ride arrives at 23:57 and the next one leaves at 23:59.
+        let mut connection = delayed_fixture(2, Some("00:00"));
+        connection.parts.remove(1);
+        connection.parts[1].from.planned_departure =
+            connection.parts[0].to.planned_departure + chrono::Duration::minutes(2);
+        assert!(connection.has_transfer_at_risk());
+        connection.parts[0].to.arrival_delay_in_minutes = Some(1);
+        assert!(!connection.has_transfer_at_risk());
+    }
 
-    let url = format!(
-        "https://www.mvg.de/api/fib/v2/connection?originStationGlobalId={}&destinationStationGlobalId={}&routingDateTime={}&routingDateTimeIsArrival={}&transportTypes={}",
-        from_station_id,
-        to_station_id,
-        time.to_rfc3339_opts(SecondsFormat::Millis, true),
-        arrival.unwrap_or(false),
-        transport_types.join(","),
-    );
+    #[test]
+    fn omitted_fields_default() {
+        // The walking leg leaves out the intermediate stops, messages,
+        // polylines and most station fields.
+        let walk = &fixture().parts[1];
+        assert!(walk.intermediate_stops.is_empty());
+        assert!(walk.messages.is_empty());
+        assert_eq!(walk.path_polyline, "");
+        assert_eq!(walk.from.place, "München");
+        assert!(walk.from.transport_types.is_empty());
+        assert!(!walk.line.sev);
+    }
+
+    #[test]
+    fn coordinates_of_leg_without_path() {
+        let walk = &fixture().parts[1];
+        assert!(walk.coordinates().is_empty());
+        assert_eq!(walk.path_length(), 900.0);
+    }
+
+    #[test]
+    fn station_detail_from_first_stop() {
+        let routes = [fixture()];
+        let detail = station_detail(&routes, "de:09162:2").unwrap();
+        assert_eq!(detail.name, "Marienplatz");
+        assert_eq!(detail.transport_types, ["UBAHN", "SBAHN"]);
+        assert!(!detail.has_out_of_order_elevator);
+    }
 
-    let resp = reqwest::get(url).await?.json::<Vec<Connection>>().await?;
-    Ok(resp)
+    #[test]
+    fn station_detail_ignores_stations_sharing_a_prefix() {
+        let mut connection = fixture();
+        connection.parts[0].from.station_global_id = "de:09162:25:1:1".to_string();
+        assert!(station_detail(&[connection], "de:09162:2").is_none());
+    }
+
+    #[test]
+    fn station_detail_matches_platforms() {
+        let mut connection = fixture();
+        connection.parts[0].from.station_global_id = "de:09162:2:52:52".to_string();
+        assert!(station_detail(&[connection], "de:09162:2").is_some());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn url_contains_time_and_arrival() {
+        use chrono::{DateTime, Local};
+
+        let time = DateTime::parse_from_rfc3339("2026-10-17T08:00:00+02:00").unwrap();
+        let url = routes_url(
+            &RoutePoint::Station("de:09162:2".to_string()),
+            &RoutePoint::Station("de:09162:50".to_string()),
+            Some(time.with_timezone(&Local)),
+            Some(true),
+            &GetRoutesConfig::default(),
+        );
+        assert!(url.contains("routingDateTime=2026-10-17T06:00:00.000Z"));
+        assert!(url.contains("routingDateTimeIsArrival=true"));
+    }
 }
@@ -1,6 +1,9 @@
-use serde::Deserialize;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug)]
+use crate::{decode::get_array, error::MvgError};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(tag = "type", rename_all = "UPPERCASE")]
 pub enum Location {
     Station(StationResp),
@@ -8,7 +11,36 @@ pub enum Location {
     Poi(PoiResponse),
 }
 
-#[derive(Deserialize, Debug)]
+impl Location {
+    /// The name of the station, address or point of interest.
+    pub fn name(&self) -> &str {
+        match self {
+            Location::Station(s) => &s.name,
+            Location::Address(a) => &a.name,
+            Location::Poi(p) => &p.name,
+        }
+    }
+
+    /// The place, e.g. "München", the location belongs to.
+    pub fn place(&self) -> &str {
+        match self {
+            Location::Station(s) => &s.place,
+            Location::Address(a) => &a.place,
+            Location::Poi(p) => &p.place,
+        }
+    }
+
+    /// The global id of a station. Addresses and points of interest have
+    /// none.
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Location::Station(s) => Some(&s.global_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StationResp {
     // #[serde(rename = "type")]
@@ -27,7 +59,7 @@ pub struct StationResp {
     pub tariff_zones: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AddressResp {
     // #[serde(rename = "type")]
@@ -42,7 +74,7 @@ pub struct AddressResp {
     // pub poi: bool,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PoiResponse {
     // #[serde(rename = "type")]
@@ -54,10 +86,76 @@ pub struct PoiResponse {
 }
 
 pub async fn get_station(station_search: &str) -> Result<Vec<Location>, reqwest::Error> {
+    get_station_with_client(&Client::new(), station_search).await
+}
+
+/// Like [`get_station`], but sends the request with `client`.
+pub async fn get_station_with_client(
+    client: &Client,
+    station_search: &str,
+) -> Result<Vec<Location>, reqwest::Error> {
     let url = format!(
         "https://www.mvg.de/api/fib/v2/location?query={}",
         station_search
     );
-    let resp = reqwest::get(url).await?.json::<Vec<Location>>().await?;
-    Ok(resp)
+    get_array(client, &url).await
+}
+
+/// Fetches the station with the given global id, e.g. `"de:09162:6"`. The
+/// API offers no lookup by id, only the location search, which finds a
+/// station by its id as well. Only a station with exactly this id is taken
+/// from the results, never a similarly named one. Fails with
+/// [`MvgError::NoStation`] if there is none.
+pub async fn get_station_by_id(global_id: &str) -> Result<Location, MvgError> {
+    get_station_by_id_with_client(&Client::new(), global_id).await
+}
+
+/// Like [`get_station_by_id`], but sends the request with `client`.
+pub async fn get_station_by_id_with_client(
+    client: &Client,
+    global_id: &str,
+) -> Result<Location, MvgError> {
+    let locations = get_station_with_client(client, global_id).await?;
+    find_by_id(locations, global_id).ok_or_else(|| MvgError::NoStation(global_id.to_string()))
+}
+
+fn find_by_id(locations: Vec<Location>, global_id: &str) -> Option<Location> {
+    locations
+        .into_iter()
+        .find(|location| location.id() == Some(global_id))
+}
+
+/// The current details of a station, including its accessibility status, see
+/// [`get_station_detail`](crate::get_station_detail).
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StationDetail {
+    pub global_id: String,
+    pub name: String,
+    pub place: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub transport_types: Vec<String>,
+    pub has_out_of_order_elevator: bool,
+    pub has_out_of_order_escalator: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_by_id, Location};
+
+    fn fixture() -> Vec<Location> {
+        serde_json::from_str(include_str!("../tests/fixtures/locations.json")).unwrap()
+    }
+
+    #[test]
+    fn finds_station_by_exact_id() {
+        let station = find_by_id(fixture(), "de:09162:500").unwrap();
+        assert_eq!(station.name(), "Münchner Freiheit");
+    }
+
+    #[test]
+    fn ignores_stations_with_other_ids() {
+        assert!(find_by_id(fixture(), "de:09162:50").is_none());
+    }
 }
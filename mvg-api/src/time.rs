@@ -0,0 +1,13 @@
+//! Timestamp representations. With the default `chrono` feature, timestamps
+//! are parsed into `chrono::DateTime<Local>`. Without it, they are kept as
+//! they come from the API: RFC 3339 strings and milliseconds since the epoch.
+
+#[cfg(feature = "chrono")]
+pub type Timestamp = chrono::DateTime<chrono::Local>;
+#[cfg(not(feature = "chrono"))]
+pub type Timestamp = String;
+
+#[cfg(feature = "chrono")]
+pub type TimestampMillis = chrono::DateTime<chrono::Local>;
+#[cfg(not(feature = "chrono"))]
+pub type TimestampMillis = i64;
@@ -0,0 +1,14 @@
+use std::path::PathBuf;
+
+/// `$XDG_CACHE_HOME/mvg`, falling back to `~/.cache/mvg`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_dir.join("mvg"))
+}
+
+/// The file caching station lookups, see [`mvg_api::cache`].
+pub fn station_cache_path() -> Option<PathBuf> {
+    Some(cache_dir()?.join("stations.json"))
+}
@@ -1,12 +1,73 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    OnceLock,
+};
+
+use mvg_api::{line, LineCategory, Occupancy, TransportType};
 use nu_ansi_term::{Color::Fixed, Style};
 
-pub fn colorize_line(line: &str) -> String {
-    if line.starts_with('U') {
-        colorized_ubahn(line)
-    } else if line.starts_with('S') {
-        colorize_sbahn(line)
+static HIGHLIGHT: OnceLock<String> = OnceLock::new();
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Emphasizes `line` in all colorized output and dims every other line.
+pub fn set_highlight(line: &str) {
+    let _ = HIGHLIGHT.set(line.to_string());
+}
+
+/// Enables or disables colors and text styles in all output.
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Paints `text` in `style`, or leaves it plain if colors are disabled.
+pub fn paint(style: impl Into<Style>, text: &str) -> String {
+    if COLOR.load(Ordering::Relaxed) {
+        style.into().paint(text).to_string()
     } else {
-        line.to_string()
+        text.to_string()
+    }
+}
+
+/// Colors a line badge by its transport type. If the type is unknown or
+/// empty, U-Bahn and S-Bahn lines are still recognized by their label.
+/// Without colors, the highlighted line is marked with "▶" instead.
+pub fn colorize_line(line: &str, transport_type: &TransportType) -> String {
+    let badge = match LineCategory::classify(line, transport_type) {
+        LineCategory::Ubahn => colorized_ubahn(line),
+        LineCategory::Sbahn => colorize_sbahn(line),
+        LineCategory::Tram => colorize_tram(line),
+        LineCategory::Bus | LineCategory::RegionalBus => colorize_bus(line),
+        _ => paint(emphasize(Style::new(), line), line),
+    };
+    if !COLOR.load(Ordering::Relaxed) && is_highlighted(line) {
+        format!("▶ {}", badge)
+    } else {
+        badge
+    }
+}
+
+/// Like [`colorize_line`], but marks lines served by a rail replacement
+/// service (Schienenersatzverkehr) with "(SEV)".
+pub fn line_badge(line: &str, transport_type: &TransportType, sev: bool) -> String {
+    let badge = colorize_line(line, transport_type);
+    if sev {
+        format!("{} {}", badge, paint(Fixed(208).bold(), "(SEV)"))
+    } else {
+        badge
+    }
+}
+
+fn is_highlighted(line: &str) -> bool {
+    HIGHLIGHT
+        .get()
+        .is_some_and(|highlight| highlight.eq_ignore_ascii_case(line))
+}
+
+fn emphasize(style: Style, line: &str) -> Style {
+    match HIGHLIGHT.get() {
+        Some(_) if is_highlighted(line) => style.bold().underline(),
+        Some(_) => style.dimmed(),
+        None => style,
     }
 }
 
@@ -18,25 +79,9 @@ fn colorized_ubahn(line: &str) -> String {
         "U4" => colorize_bg(line, 30),
         "U5" => colorize_bg(line, 94),
         "U6" => colorize_bg(line, 20),
-        "U7" => {
-            let mut i = line.chars();
-            let lhs = i.next().unwrap();
-            let rhs = i.next().unwrap();
-            let lhs = Fixed(255).on(Fixed(22)).paint(format!(" {}", lhs));
-            let rhs = Fixed(255).on(Fixed(124)).paint(format!("{} ", rhs));
-            let total = [lhs.to_string(), rhs.to_string()].join("");
-            Style::new().paint(total).to_string()
-        }
-        "U8" => {
-            let mut i = line.chars();
-            let lhs = i.next().unwrap();
-            let rhs = i.next().unwrap();
-            let lhs = Fixed(255).on(Fixed(124)).paint(format!(" {}", lhs));
-            let rhs = Fixed(255).on(Fixed(166)).paint(format!("{} ", rhs));
-            let total = [lhs.to_string(), rhs.to_string()].join("");
-            Style::new().paint(total).to_string()
-        }
-        _ => line.to_string(),
+        "U7" => colorize_split_bg(line, 22, 124),
+        "U8" => colorize_split_bg(line, 124, 166),
+        _ => paint(emphasize(Style::new(), line), line),
     }
 }
 
@@ -48,18 +93,82 @@ fn colorize_sbahn(line: &str) -> String {
         "S4" => colorize_bg(line, 196),
         "S6" => colorize_bg(line, 29),
         "S7" => colorize_bg(line, 204),
-        "S8" => Fixed(226)
-            .on(Fixed(233))
-            .paint(format!(" {} ", line))
-            .to_string(),
+        "S8" => paint(
+            emphasize(Fixed(226).on(Fixed(233)), line),
+            &format!(" {} ", line),
+        ),
         "S20" => colorize_bg(line, 203),
-        _ => line.to_string(),
+        _ => paint(emphasize(Style::new(), line), line),
+    }
+}
+
+fn colorize_tram(line: &str) -> String {
+    match line {
+        "12" => colorize_bg(line, 90),
+        "16" => colorize_bg(line, 25),
+        "17" => colorize_bg(line, 94),
+        "18" => colorize_bg(line, 34),
+        "19" => colorize_bg(line, 160),
+        "20" => colorize_bg(line, 38),
+        "21" => colorize_bg(line, 136),
+        "23" => colorize_bg(line, 148),
+        "25" => colorize_bg(line, 211),
+        "27" | "28" => colorize_bg(line, 214),
+        _ => colorize_bg(line, 124),
+    }
+}
+
+/// MetroBus lines (50 to 69) are orange, express buses green and all other
+/// buses a muted teal.
+fn colorize_bus(line: &str) -> String {
+    match line.parse::<u32>() {
+        Ok(50..=69) => colorize_bg(line, 208),
+        _ if line::is_express(line) => colorize_bg(line, 28),
+        _ => colorize_bg(line, 23),
     }
 }
 
 fn colorize_bg(line: &str, background_color: u8) -> String {
-    Fixed(255)
-        .on(Fixed(background_color))
-        .paint(format!(" {} ", line))
-        .to_string()
+    paint(
+        emphasize(Fixed(255).on(Fixed(background_color)), line),
+        &format!(" {} ", line),
+    )
+}
+
+fn colorize_split_bg(line: &str, lhs_color: u8, rhs_color: u8) -> String {
+    let mut i = line.chars();
+    let lhs = i.next().unwrap();
+    let rhs = i.next().unwrap();
+    let lhs = paint(
+        emphasize(Fixed(255).on(Fixed(lhs_color)), line),
+        &format!(" {}", lhs),
+    );
+    let rhs = paint(
+        emphasize(Fixed(255).on(Fixed(rhs_color)), line),
+        &format!("{} ", rhs),
+    );
+    [lhs, rhs].join("")
+}
+
+/// The bars shown for the known occupancies, with their colors.
+const OCCUPANCY_BARS: [(Occupancy, &str, u8); 3] = [
+    (Occupancy::Low, "█", 34),
+    (Occupancy::Medium, "██", 220),
+    (Occupancy::High, "███", 196),
+];
+
+/// A colored bar for a known occupancy, or a dash if it is unknown.
+pub fn colorize_occupancy(occupancy: Occupancy) -> String {
+    match OCCUPANCY_BARS.iter().find(|(o, _, _)| *o == occupancy) {
+        Some((_, bar, color)) => paint(Fixed(*color), bar),
+        None => "-".to_string(),
+    }
+}
+
+/// The occupancy shown by the plain text of [`colorize_occupancy`].
+pub fn occupancy_of_bar(bar: &str) -> Occupancy {
+    OCCUPANCY_BARS
+        .iter()
+        .find(|(_, b, _)| *b == bar)
+        .map_or(Occupancy::Unknown, |(occupancy, _, _)| *occupancy)
 }
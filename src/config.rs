@@ -0,0 +1,35 @@
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Arguments to run when `mvg` is invoked without a subcommand,
+    /// e.g. `["departures", "Marienplatz"]`
+    pub default_command: Option<Vec<String>>,
+}
+
+impl Config {
+    /// Loads the config from `$XDG_CONFIG_HOME/mvg/config.json`, falling back
+    /// to `~/.config/mvg/config.json`. A missing file yields the default config.
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_path() else {
+            return Ok(Config::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid config file {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e).with_context(|| format!("Cannot read {}", path.display())),
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("mvg").join("config.json"))
+}
@@ -0,0 +1,41 @@
+use std::fmt::Write;
+
+use mvg_api::routes::Connection;
+
+/// Writes the paths of a connection as a GPX document with one track per
+/// leg. The walking path of a change, if any, is added to the leg as a
+/// second track segment.
+pub fn to_gpx(connection: &Connection) -> String {
+    let mut gpx = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<gpx version=\"1.1\" creator=\"mvg-cli\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+    ));
+    for part in &connection.parts {
+        let name = format!("{}: {} - {}", part.line.label, part.from.name, part.to.name);
+        let _ = writeln!(gpx, "  <trk>\n    <name>{}</name>", escape(&name));
+        for coordinates in [part.coordinates(), part.interchange_coordinates()] {
+            if coordinates.is_empty() {
+                continue;
+            }
+            gpx.push_str("    <trkseg>\n");
+            for (latitude, longitude) in coordinates {
+                let _ = writeln!(
+                    gpx,
+                    "      <trkpt lat=\"{:.5}\" lon=\"{:.5}\"/>",
+                    latitude, longitude
+                );
+            }
+            gpx.push_str("    </trkseg>\n");
+        }
+        gpx.push_str("  </trk>\n");
+    }
+    gpx.push_str("</gpx>\n");
+    gpx
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
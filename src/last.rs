@@ -0,0 +1,35 @@
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::cache;
+
+/// Loads the arguments of the last successful invocation from
+/// `$XDG_CACHE_HOME/mvg/last.json`, falling back to `~/.cache/mvg/last.json`.
+pub fn load() -> Result<Option<Vec<String>>> {
+    let Some(path) = last_path() else {
+        return Ok(None);
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content)
+            .with_context(|| format!("Invalid last command in {}", path.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Cannot read {}", path.display())),
+    }
+}
+
+/// Stores the arguments, without the program name, to be repeated by `mvg last`.
+pub fn save(args: &[String]) -> Result<()> {
+    let Some(path) = last_path() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+    }
+    fs::write(&path, serde_json::to_string(args)?)
+        .with_context(|| format!("Cannot write {}", path.display()))
+}
+
+fn last_path() -> Option<PathBuf> {
+    Some(cache::cache_dir()?.join("last.json"))
+}
@@ -1,24 +1,156 @@
+mod cache;
 mod colorize;
+mod config;
+mod gpx;
+mod last;
 
-use anyhow::Result;
-use chrono::{Local, NaiveTime, TimeZone};
-use clap::{Parser, Subcommand};
-use mvg_api::{get_departures, get_notifications, get_routes, get_station, Location};
-use nu_ansi_term::Style;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    io::{IsTerminal, Write},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Timelike};
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    Args, CommandFactory, Parser, Subcommand, ValueEnum,
+};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use mvg_api::{
+    cache::{clear_station_cache, get_station_cached},
+    data_problems,
+    departures::Departure,
+    get_departures_with_client, get_notifications_with_client, get_routes_with_client,
+    get_station_detail_with_client, get_station_with_client,
+    notifications::{Notification, NotificationLines, NotificationLink},
+    routes::{Connection, TicketingInformation},
+    set_strict, skipped_elements, Client, GetRoutesConfig, Location, MvgError, Occupancy,
+    RoutePoint, TransportType,
+};
+use nu_ansi_term::{Color::Fixed, Style};
+use serde::Serialize;
 use spinners::{Spinner, Spinners};
 use tabled::{
-    settings::{object::Columns, Modify, Width},
+    grid::util::string::string_width,
+    settings::{
+        locator::ByColumnName, object::Columns, style::HorizontalLine, Disable, Modify, Width,
+    },
     Table, Tabled,
 };
 use terminal_size::{terminal_size, Width as TerminalWidth};
 
-use crate::colorize::colorize_line;
+use crate::{
+    colorize::{colorize_line, colorize_occupancy, line_badge, occupancy_of_bar, paint},
+    config::Config,
+};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_override_self = true)]
 struct Cli {
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+    /// Shorthand for --format json
+    #[arg(long, global = true)]
+    json: bool,
+    /// When to use colors; auto disables them if stdout is not a terminal or NO_COLOR is set
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Emphasize a line, e.g. U6, and dim all others
+    #[arg(long, global = true)]
+    highlight: Option<String>,
+    /// Wrap output to this many columns instead of the terminal width
+    #[arg(long, global = true)]
+    width: Option<usize>,
+    /// Report malformed elements skipped in API responses
+    #[arg(long, global = true)]
+    debug: bool,
+    /// Fail if API responses contain malformed elements or missing fields
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Give up on a request after this many seconds
+    #[arg(
+        long,
+        global = true,
+        value_name = "SECONDS",
+        default_value_t = 15,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    timeout: u64,
+    /// Look up stations without the station cache
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// Use this RFC 3339 timestamp instead of the current time
+    #[arg(long, global = true, hide = true, value_parser = parse_now)]
+    now: Option<DateTime<Local>>,
+}
+
+/// Options shared by all subcommands.
+struct Context {
+    format: OutputFormat,
+    width: Option<usize>,
+    now: Option<DateTime<Local>>,
+    /// Shared by all requests, so connections are reused.
+    client: Client,
+    /// The station cache file, unless disabled with `--no-cache`.
+    station_cache: Option<PathBuf>,
+    /// Whether to report details on the API responses, set by `--debug`.
+    debug: bool,
+}
+
+impl Context {
+    /// The current time, unless overridden with `--now`.
+    fn now(&self) -> DateTime<Local> {
+        self.now.unwrap_or_else(Local::now)
+    }
+
+    /// The output width, unless overridden with `--width` the width of the
+    /// terminal, or 80 if not in a terminal.
+    fn width(&self) -> usize {
+        self.width.unwrap_or_else(|| {
+            terminal_size()
+                .map(|(TerminalWidth(w), _)| w as usize)
+                .unwrap_or(80)
+        })
+    }
+}
+
+fn parse_now(now: &str) -> Result<DateTime<Local>> {
+    Ok(DateTime::parse_from_rfc3339(now)?.with_timezone(&Local))
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Table,
+    Json,
+    JsonCompact,
+    Csv,
+    Tsv,
+}
+
+impl OutputFormat {
+    fn is_json(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::JsonCompact)
+    }
+
+    /// The field delimiter of the CSV and TSV formats.
+    fn delimiter(self) -> Option<u8> {
+        match self {
+            OutputFormat::Csv => Some(b','),
+            OutputFormat::Tsv => Some(b'\t'),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -26,35 +158,48 @@ struct Cli {
 enum Commands {
     /// Show routes
     #[clap(visible_alias = "r")]
-    Routes {
-        /// The station from where to go
-        from: String,
-        /// The station of destination
-        to: String,
-        /// Specify a time in [HH:MM] for the departure or arrival if -a
-        #[arg(short, long)]
-        time: Option<String>,
-        /// If set, --time specifies the arrival time
-        #[arg(short, long, requires = "time")]
-        arrival: bool,
-    },
+    Routes(RoutesArgs),
 
     /// Show Departures
     #[clap(visible_alias = "d")]
-    Departures {
-        /// The station from where depart
-        station: String,
-        /// Specify a time offset in minutes
-        #[arg(short, long)]
-        offset: Option<usize>,
-    },
+    Departures(DeparturesArgs),
 
     /// Show all notifications or for a specific line
     #[clap(visible_alias = "n")]
-    Notifications {
-        /// Filter for a specific line
-        #[arg(short, long)]
-        filter: Option<String>,
+    Notifications(NotificationsArgs),
+
+    /// Show all stations, addresses and POIs matching a search
+    #[clap(visible_alias = "search")]
+    Stations {
+        /// The search query
+        query: String,
+    },
+
+    /// Show whether elevators and escalators of a station are out of order
+    Elevator {
+        /// The station to check
+        station: String,
+        /// Only consider stations whose place contains this, e.g. München
+        #[arg(long)]
+        place: Option<String>,
+    },
+
+    /// Show the plan of the surroundings of a station in the browser
+    Plan {
+        /// The station whose surroundings to show
+        station: String,
+        /// Only consider stations whose place contains this, e.g. München
+        #[arg(long)]
+        place: Option<String>,
+    },
+
+    /// Repeat the last successful command
+    Last,
+
+    /// Manage the station cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
     },
 
     /// Show map in browser
@@ -72,24 +217,522 @@ enum Commands {
     },
 }
 
+#[derive(Args, Debug)]
+struct RoutesArgs {
+    /// The station from where to go
+    from: String,
+    /// The station of destination
+    to: String,
+    /// Specify a time in [HH:MM] or [HH:MM AM/PM] for the departure or arrival if -a,
+    /// or relative to now, e.g. +20m, +1h30m or "in 45min"
+    #[arg(short, long)]
+    time: Option<String>,
+    /// Plan the connection for this day, in [YYYY-MM-DD] or [DD.MM.YYYY]
+    #[arg(long)]
+    date: Option<String>,
+    /// If set, --time specifies the arrival time
+    #[arg(short, long, requires = "time")]
+    arrival: bool,
+    /// Only show connections delayed by at least this many minutes
+    #[arg(long, value_name = "MINUTES")]
+    delay_threshold: Option<isize>,
+    /// Only show connections leaving now or within the given minutes
+    #[arg(long, value_name = "MINUTES", conflicts_with_all = ["time", "date"])]
+    leave_in: Option<i64>,
+    /// Show all connections departing within this many minutes after the
+    /// given time or now
+    #[arg(
+        long,
+        value_name = "MINUTES",
+        conflicts_with = "arrival",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    within: Option<u32>,
+    /// Show at most this many connections, or all if 0 [default: 5, or all
+    /// with --within]
+    #[arg(short = 'n', long)]
+    limit: Option<usize>,
+    /// Show all connections, like --limit 0
+    #[arg(long, conflicts_with = "limit")]
+    all: bool,
+    /// Sort the connections by their duration, shortest first
+    #[arg(long, conflicts_with_all = ["soonest", "group_by_hour"])]
+    fastest: bool,
+    /// Sort the connections by their departure time (default)
+    #[arg(long)]
+    soonest: bool,
+    /// Only show connections with at most this many changes
+    #[arg(long, value_name = "N")]
+    max_changes: Option<usize>,
+    /// Only show connections without changes, like --max-changes 0
+    #[arg(long, conflicts_with = "max_changes")]
+    direct: bool,
+    /// Hide connections with a walk longer than this many meters
+    #[arg(long, value_name = "METERS")]
+    max_walk: Option<f64>,
+    /// Interpret origin and destination as addresses
+    #[arg(long, conflicts_with = "station")]
+    address: bool,
+    /// Interpret origin and destination as station names
+    #[arg(long)]
+    station: bool,
+    /// Only consider origins whose place contains this, e.g. München
+    #[arg(long)]
+    from_place: Option<String>,
+    /// Only consider destinations whose place contains this, e.g. Freising
+    #[arg(long)]
+    to_place: Option<String>,
+    /// Only show the first and last line and the number of changes
+    #[arg(long)]
+    compact_lines: bool,
+    /// Only print a JSON summary of the next connection
+    #[arg(long)]
+    summary_json: bool,
+    /// Only print the next connection on one line
+    #[arg(long, conflicts_with = "summary_json")]
+    next: bool,
+    /// Show the total distance of each connection
+    #[arg(long)]
+    show_distance: bool,
+    /// Hide connections with an out-of-order elevator at a station where
+    /// they start, end or change
+    #[arg(long, visible_alias = "accessible")]
+    step_free: bool,
+    /// List the stops of each connection after the table
+    #[arg(long)]
+    stops: bool,
+    /// Show the global ids of origin and destination
+    #[arg(long)]
+    print_ids: bool,
+    /// Don't show additional infos, e.g. on accessibility, next to messages
+    #[arg(long)]
+    no_infos: bool,
+    /// Open the next connection in a maps application
+    #[arg(long, value_enum, value_name = "PROVIDER", num_args = 0..=1, default_missing_value = "google")]
+    open_maps: Option<MapsProvider>,
+    /// Write the paths of the next connection to a GPX file
+    #[arg(long, value_name = "FILE")]
+    gpx: Option<PathBuf>,
+    /// Warn about connections using lines with active notifications
+    #[arg(long)]
+    with_alternatives: bool,
+    /// Group the connections by the hour of their departure
+    #[arg(long)]
+    group_by_hour: bool,
+    /// Show one row per leg instead of per connection
+    #[arg(long, conflicts_with = "compact_lines")]
+    table_by_leg: bool,
+    /// Avoid the given transport types, e.g. --avoid bus,tram
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "prefer")]
+    avoid: Vec<TransportMode>,
+    /// Only use the given transport types, e.g. --prefer ubahn,sbahn
+    #[arg(long, value_enum, value_delimiter = ',', visible_alias = "transport")]
+    prefer: Vec<TransportMode>,
+    /// Don't use regional buses
+    #[arg(long)]
+    no_regional_bus: bool,
+}
+
+impl RoutesArgs {
+    fn location_mode(&self) -> LocationMode {
+        match (self.address, self.station) {
+            (true, _) => LocationMode::Address,
+            (_, true) => LocationMode::Station,
+            _ => LocationMode::Auto,
+        }
+    }
+
+    fn includes(&self, transport_mode: TransportMode) -> bool {
+        if !self.prefer.is_empty() {
+            self.prefer.contains(&transport_mode)
+        } else {
+            !self.avoid.contains(&transport_mode)
+        }
+    }
+
+    fn routes_config(&self) -> GetRoutesConfig {
+        GetRoutesConfig {
+            include_ubahn: self.includes(TransportMode::Ubahn),
+            include_bus: self.includes(TransportMode::Bus),
+            include_tram: self.includes(TransportMode::Tram),
+            include_sbahn: self.includes(TransportMode::Sbahn),
+            include_taxi: self.includes(TransportMode::Taxi),
+            include_regional_bus: self.includes(TransportMode::RegionalBus)
+                && !self.no_regional_bus,
+            include_ferry: self.includes(TransportMode::Ferry),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct DeparturesArgs {
+    /// The station from where depart, searched interactively if omitted
+    station: Option<String>,
+    /// Specify a time offset in minutes
+    #[arg(short, long)]
+    offset: Option<usize>,
+    /// Only consider stations whose place contains this, e.g. München
+    #[arg(long)]
+    place: Option<String>,
+    /// Show at most this many departures, or all if 0
+    #[arg(short = 'n', long, default_value_t = 10)]
+    limit: usize,
+    /// Show a full-screen departure board that refreshes periodically
+    #[arg(long)]
+    board: bool,
+    /// Refresh the departures on space or enter and quit on q
+    #[arg(long, visible_alias = "refresh-on-keypress", conflicts_with = "board")]
+    interactive: bool,
+    /// Show the global id of the station
+    #[arg(long)]
+    print_ids: bool,
+    /// Redraw the departures table periodically until Ctrl-C is pressed
+    #[arg(long, conflicts_with_all = ["board", "interactive"])]
+    watch: bool,
+    /// Seconds between the refreshes of --watch and --board
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 30,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    interval: u64,
+    /// Show whether delays grew since the last refresh of --board, --interactive or --watch
+    #[arg(long)]
+    since_last: bool,
+    /// Show departures from different stops or platforms in separate tables
+    #[arg(long)]
+    group_stops: bool,
+    /// Hide cancelled departures
+    #[arg(long)]
+    hide_cancelled: bool,
+    /// Hide departures expected to be fuller than this; those without data
+    /// are kept
+    #[arg(
+        long,
+        value_name = "OCCUPANCY",
+        value_parser = PossibleValuesParser::new(["low", "medium", "high"]).map(Occupancy::from)
+    )]
+    max_occupancy: Option<Occupancy>,
+    /// Only show cancelled departures or those delayed by at least this many minutes
+    #[arg(long, value_name = "MINUTES")]
+    delay_threshold: Option<isize>,
+    /// Order departures by their realtime instead of their planned departure
+    #[arg(long)]
+    interleave_realtime: bool,
+    /// Show the realtime departure with its delay, e.g. 20:05 (+3)
+    #[arg(long)]
+    realtime: bool,
+    /// Sort the departures by time, line or destination
+    #[arg(long, value_enum, default_value_t = DepartureSort::Time)]
+    sort: DepartureSort,
+    /// Show how many departures each line has
+    #[arg(long, conflicts_with_all = ["board", "interactive", "watch"])]
+    count_by_line: bool,
+}
+
+#[derive(Args, Debug)]
+struct NotificationsArgs {
+    #[command(subcommand)]
+    action: Option<NotificationsAction>,
+    /// Filter for a specific line
+    #[arg(short, long)]
+    filter: Option<String>,
+    /// Only show notifications affecting this station
+    #[arg(long)]
+    station: Option<String>,
+    /// Only show notifications that are currently in effect
+    #[arg(long, conflicts_with = "upcoming")]
+    active: bool,
+    /// Only show notifications that take effect in the future
+    #[arg(long)]
+    upcoming: bool,
+    /// Only show notifications of this provider, e.g. MVG
+    #[arg(long)]
+    provider: Option<String>,
+    /// Only show notifications of this type, e.g. DISRUPTION
+    #[arg(long = "type", value_name = "TYPE")]
+    type_name: Option<String>,
+    /// Only show notifications with this event type
+    #[arg(long)]
+    event_type: Option<String>,
+    /// Show at most N notifications, newest first
+    #[arg(short, long)]
+    max: Option<usize>,
+    /// Show all notifications, overriding --max
+    #[arg(long)]
+    all: bool,
+    /// Group notifications by the lines they affect
+    #[arg(long)]
+    by_line: bool,
+    /// Merge notifications with the same content into one entry
+    #[arg(long, conflicts_with = "by_line")]
+    condensed: bool,
+    /// With --condensed, also merge notifications sharing this ratio of words, e.g. 0.8
+    #[arg(long, requires = "condensed")]
+    similarity: Option<f64>,
+    /// Poll every N seconds and print only notifications that are new
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    watch: Option<u64>,
+    /// Also show new notifications of --watch as desktop notifications
+    #[cfg(feature = "desktop-notifications")]
+    #[arg(long, requires = "watch")]
+    desktop: bool,
+}
+
+impl NotificationsArgs {
+    fn max(&self) -> Option<usize> {
+        if self.all {
+            None
+        } else {
+            self.max
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheAction {
+    /// Remove all cached station lookups
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+enum NotificationsAction {
+    /// Show the full text of a notification
+    Show {
+        /// The number of the notification as listed
+        index: usize,
+    },
+    /// Open a link of a notification in the browser
+    Open {
+        /// The number of the notification as listed
+        index: usize,
+        /// The number of the link, if the notification has several
+        link: Option<usize>,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum TransportMode {
+    Ubahn,
+    Bus,
+    Tram,
+    Sbahn,
+    Taxi,
+    RegionalBus,
+    /// Ferries on the lakes, as `SCHIFF` in the API
+    #[value(alias = "schiff")]
+    Ferry,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum MapsProvider {
+    Google,
+    Apple,
+    Osm,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum DepartureSort {
+    Time,
+    Line,
+    Destination,
+}
+
+/// An error that ends `mvg` with its own exit code, so scripts can tell
+/// common failures apart. Other errors exit with 1, invalid arguments with 2.
+#[derive(Debug)]
+enum Failure {
+    /// No station or address matches a search; exit code 3.
+    UnknownStation(String),
+    /// No connection or other result matches; exit code 4.
+    NothingFound(String),
+    /// A request failed or timed out; exit code 5.
+    Network(String),
+}
+
+impl Failure {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Failure::UnknownStation(_) => 3,
+            Failure::NothingFound(_) => 4,
+            Failure::Network(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::UnknownStation(message)
+            | Failure::NothingFound(message)
+            | Failure::Network(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// The arguments for `mvg last`: the stored `last_args`, followed by the
+/// flags given with `mvg last` now, which override the stored ones.
+fn last_argv(argv: &[String], last_args: Vec<String>) -> Vec<String> {
+    let last = argv
+        .iter()
+        .skip(1)
+        .position(|arg| arg == "last")
+        .map(|i| i + 1);
+    let flags = argv
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(i, _)| Some(*i) != last)
+        .map(|(_, arg)| arg.clone());
+    argv[..1]
+        .iter()
+        .cloned()
+        .chain(last_args)
+        .chain(flags)
+        .collect()
+}
+
+/// The exit code for `error`: the one of a [`Failure`] in its chain, 5 for
+/// other failed requests and 1 for everything else.
+fn exit_code(error: &anyhow::Error) -> u8 {
+    for cause in error.chain() {
+        if let Some(failure) = cause.downcast_ref::<Failure>() {
+            return failure.exit_code();
+        }
+        if cause.downcast_ref::<mvg_api::Error>().is_some() {
+            return 5;
+        }
+    }
+    1
+}
+
 #[tokio::main]
-async fn main() -> Result<()> {
-    let args: Cli = Cli::parse();
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
 
-    match args.command {
-        Commands::Routes {
-            from,
-            to,
-            time,
-            arrival,
-        } => {
-            handle_routes(from, to, time, arrival).await?;
+async fn run() -> Result<()> {
+    let mut argv = std::env::args().collect::<Vec<_>>();
+    let mut args: Cli = Cli::parse_from(&argv);
+
+    if args.command.is_none() {
+        if let Some(default_command) = Config::load()?.default_command {
+            argv.extend(default_command);
+            args = Cli::parse_from(&argv);
+        }
+    }
+    if let Some(Commands::Last) = args.command {
+        let Some(last_args) = last::load()? else {
+            anyhow::bail!("No previous command to repeat, run e.g. `mvg d Marienplatz` first");
+        };
+        argv = last_argv(&argv, last_args);
+        args = Cli::parse_from(&argv);
+        if let Some(Commands::Last) = args.command {
+            anyhow::bail!("The last command cannot be `mvg last` itself");
+        }
+    }
+    let Some(command) = args.command else {
+        Cli::command().print_help()?;
+        return Ok(());
+    };
+    colorize::set_color(args.color.enabled());
+    if let Some(line) = &args.highlight {
+        colorize::set_highlight(line);
+    }
+    set_strict(args.strict);
+    let ctx = Context {
+        format: if args.json {
+            OutputFormat::Json
+        } else {
+            args.format
+        },
+        width: args.width,
+        now: args.now,
+        client: Client::builder()
+            .timeout(Duration::from_secs(args.timeout))
+            .build()?,
+        station_cache: if args.no_cache {
+            None
+        } else {
+            cache::station_cache_path()
+        },
+        debug: args.debug,
+    };
+
+    if let Err(e) = run_command(command, &ctx).await {
+        let timed_out = e.chain().any(|cause| {
+            cause
+                .downcast_ref::<mvg_api::Error>()
+                .is_some_and(|e| e.is_timeout())
+        });
+        if timed_out {
+            anyhow::bail!(Failure::Network(format!(
+                "Request timed out after {}s",
+                args.timeout
+            )));
+        }
+        return Err(e);
+    }
+    if args.debug && skipped_elements() > 0 {
+        eprintln!(
+            "Skipped {} malformed elements in API responses",
+            skipped_elements()
+        );
+    }
+    let problems = data_problems();
+    if !problems.is_empty() {
+        anyhow::bail!("Malformed API responses:\n{}", problems.join("\n"));
+    }
+    if let Err(e) = last::save(&argv[1..]) {
+        eprintln!("Cannot remember this command for `mvg last`: {:#}", e);
+    }
+
+    Ok(())
+}
+
+async fn run_command(command: Commands, ctx: &Context) -> Result<()> {
+    match command {
+        Commands::Routes(routes_args) => {
+            handle_routes(routes_args, ctx).await?;
         }
-        Commands::Notifications { filter } => {
-            handle_notifications(filter).await?;
+        Commands::Notifications(notifications_args) => {
+            handle_notifications(notifications_args, ctx).await?;
         }
-        Commands::Departures { station, offset } => {
-            handle_departures(station, offset).await?;
+        Commands::Departures(departures_args) => {
+            handle_departures(departures_args, ctx).await?;
         }
         Commands::Map {
             region,
@@ -98,7 +741,25 @@ async fn main() -> Result<()> {
         } => {
             handle_map(region, tram, night)?;
         }
-    };
+        Commands::Stations { query } => {
+            handle_stations(&query, ctx).await?;
+        }
+        Commands::Elevator { station, place } => {
+            handle_elevator(&station, place.as_deref(), ctx).await?;
+        }
+        Commands::Plan { station, place } => {
+            open_surrounding_plan(&station, place.as_deref(), ctx).await?;
+        }
+        Commands::Cache {
+            action: CacheAction::Clear,
+        } => {
+            if let Some(path) = cache::station_cache_path() {
+                clear_station_cache(&path)
+                    .with_context(|| format!("Cannot remove {}", path.display()))?;
+            }
+        }
+        Commands::Last => anyhow::bail!("`mvg last` cannot be repeated"),
+    }
 
     Ok(())
 }
@@ -115,80 +776,339 @@ struct RouteTableEntry {
     lines: String,
     #[tabled(rename = "Delay")]
     delay: String,
+    #[tabled(rename = "Distance")]
+    distance: String,
+    #[tabled(rename = "Zones")]
+    zones: String,
     #[tabled(rename = "Info")]
     info: String,
 }
 
-async fn handle_routes(
-    from: String,
-    to: String,
-    time: Option<String>,
-    arrival: bool,
-) -> Result<()> {
-    let mut spinner = Spinner::new(Spinners::Aesthetic, "Fetching...".to_string());
-    let from_response = &get_station(&from).await?[0];
-    let from_id = match from_response {
-        mvg_api::Location::Station(s) => &s.global_id,
-        _ => anyhow::bail!("No station {} found", from),
+async fn handle_routes(args: RoutesArgs, ctx: &Context) -> Result<()> {
+    let format = match ctx.format {
+        OutputFormat::Table if args.summary_json => OutputFormat::Json,
+        format => format,
     };
-    let to_response = &get_station(&to).await?[0];
-    let to_id = match to_response {
-        mvg_api::Location::Station(s) => &s.global_id,
-        _ => anyhow::bail!("No station {} found", to),
+    let time = routing_time(&args, ctx.now())?;
+    let mode = args.location_mode();
+    let from_response =
+        &resolve_location(ctx, &args.from, mode, args.from_place.as_deref()).await?;
+    let from_point = route_point_from_location(from_response);
+    let to_response = &resolve_location(ctx, &args.to, mode, args.to_place.as_deref()).await?;
+    let to_point = route_point_from_location(to_response);
+    let spinner = start_spinner(format);
+
+    let routes_config = args.routes_config();
+    let (mut routes, notifications) = tokio::try_join!(
+        async {
+            match args.within {
+                Some(minutes) => {
+                    get_routes_within(ctx, &from_point, &to_point, time, minutes, &routes_config)
+                        .await
+                }
+                None => {
+                    get_routes_with_client(
+                        &ctx.client,
+                        &from_point,
+                        &to_point,
+                        Some(time),
+                        Some(args.arrival),
+                        &routes_config,
+                    )
+                    .await
+                }
+            }
+        },
+        async {
+            if args.with_alternatives {
+                get_notifications_with_client(&ctx.client).await
+            } else {
+                Ok(Vec::new())
+            }
+        }
+    )?;
+    dedup_connections(&mut routes, ctx.debug);
+    let disrupted_lines = active_notification_lines(&notifications, ctx.now());
+    if args.time.is_none() && args.date.is_none() {
+        let now = ctx.now();
+        routes.retain(|connection| !connection.has_departed(now));
+    }
+    if let Some(threshold) = args.delay_threshold {
+        routes.retain(|connection| {
+            connection.origin().departure_delay_in_minutes.unwrap_or(0) >= threshold
+        });
+    }
+    if let Some(max_changes) = args.max_changes.or(args.direct.then_some(0)) {
+        routes.retain(|connection| connection.num_changes() <= max_changes);
+    }
+    if let Some(max_walk) = args.max_walk {
+        retain_max_walk(&mut routes, max_walk);
+    }
+    if args.step_free {
+        routes.retain(|connection| connection.stations_with_broken_elevator().is_empty());
+    }
+    if let Some(minutes) = args.leave_in {
+        let latest = ctx.now() + chrono::Duration::minutes(minutes);
+        routes.retain(|connection| connection.origin().planned_departure <= latest);
+        if routes.is_empty() {
+            anyhow::bail!(Failure::NothingFound(format!(
+                "No connection leaves within {} minutes, try a larger --leave-in",
+                minutes
+            )));
+        }
+    }
+    ensure_found(&routes, "connection")?;
+    if let Some(provider) = args.open_maps {
+        open::that(maps_url(&routes[0], provider))?;
+    }
+    if let Some(path) = &args.gpx {
+        std::fs::write(path, gpx::to_gpx(&routes[0]))
+            .with_context(|| format!("Cannot write {}", path.display()))?;
+    }
+    if args.summary_json {
+        return print_json(&TripSummary::from(&routes[0]), format);
+    }
+    if args.next {
+        let Some(connection) = routes.iter().min_by_key(|c| c.origin().planned_departure) else {
+            anyhow::bail!(Failure::NothingFound("No connection found".to_string()));
+        };
+        if format.is_json() {
+            return print_json(&ConnectionJson::from(connection), format);
+        }
+        stop_spinner(spinner, "Next connection:".to_string());
+        println!("{}", next_connection_line(connection));
+        return Ok(());
+    }
+    routes.sort_by_key(|connection| connection.origin().planned_departure);
+    if args.fastest {
+        routes.sort_by_key(Connection::total_duration);
+    }
+    let limit = match (args.limit, args.within) {
+        (Some(limit), _) => limit,
+        (None, Some(_)) => 0,
+        (None, None) => 5,
     };
-    let time = match time {
-        Some(t) => {
-            let naive_time = NaiveTime::parse_from_str(&t, "%H:%M")?;
-            let naive_datetime = Local::now().date_naive().and_time(naive_time);
-            Local.from_local_datetime(&naive_datetime).unwrap()
+    if !args.all && limit > 0 {
+        routes.truncate(limit);
+    }
+    if format.is_json() {
+        let routes = routes.iter().map(ConnectionJson::from).collect::<Vec<_>>();
+        return print_json(&routes, format);
+    }
+    let table = |routes: &[Connection]| {
+        if args.table_by_leg {
+            legs_table(routes)
+        } else {
+            routes_table(routes, &args, ctx.now(), &disrupted_lines)
         }
-        None => Local::now(),
     };
+    if format != OutputFormat::Table {
+        return print_table(&table(&routes), format);
+    }
+    let from_name = status_name(from_response, args.print_ids);
+    let to_name = status_name(to_response, args.print_ids);
+    stop_spinner(
+        spinner,
+        format!("Connections for: {} ➜ {}", from_name, to_name),
+    );
+    if args.group_by_hour {
+        let hour = |c: &Connection| {
+            let departure = c.origin().planned_departure;
+            (departure.date_naive(), departure.hour())
+        };
+        for group in routes.chunk_by(|a, b| hour(a) == hour(b)) {
+            let (_, h) = hour(&group[0]);
+            let header = format!("{:02}:00–{:02}:00", h, (h + 1) % 24);
+            println!("{}", paint(Style::new().bold(), &header));
+            println!("{}", table(group));
+        }
+    } else {
+        println!("{}", table(&routes));
+    }
+    if args.stops {
+        print_stops(&routes);
+    }
 
-    let routes = get_routes(
-        from_id,
-        to_id,
-        Some(time),
-        Some(arrival),
-        None,
-        None,
-        None,
-        None,
-        None,
-    )
-    .await?;
+    Ok(())
+}
+
+/// Fails with [`Failure::NothingFound`] if there are no `items`, so that
+/// scripts can tell an empty result from a successful one.
+fn ensure_found<T>(items: &[T], what: &str) -> Result<()> {
+    if items.is_empty() {
+        anyhow::bail!(Failure::NothingFound(format!("No {} found", what)));
+    }
+    Ok(())
+}
+
+/// Drops connections with a walk longer than `max_walk` meters, for
+/// `--max-walk`.
+fn retain_max_walk(routes: &mut Vec<Connection>, max_walk: f64) {
+    routes.retain(|connection| {
+        !connection
+            .parts
+            .iter()
+            .any(|part| part.is_walk() && part.distance > max_walk)
+    });
+}
+
+/// Drops connections listed again with the same `unique_id` and ticketing,
+/// keeping the first. Connections sharing an id but not their ticketing are
+/// kept, and reported if `debug` is set.
+fn dedup_connections(routes: &mut Vec<Connection>, debug: bool) {
+    let mut kept: Vec<Connection> = Vec::with_capacity(routes.len());
+    for connection in routes.drain(..) {
+        match kept.iter().find(|c| c.unique_id == connection.unique_id) {
+            Some(c) if c.ticketing_information == connection.ticketing_information => continue,
+            Some(_) if debug => eprintln!(
+                "Connection {} is listed twice with different ticketing",
+                connection.unique_id
+            ),
+            _ => {}
+        }
+        kept.push(connection);
+    }
+    *routes = kept;
+}
+
+/// The most requests `--within` sends to cover its time window.
+const MAX_WITHIN_REQUESTS: usize = 6;
+
+/// Fetches the connections departing within `minutes` after `time`. As each
+/// request only returns a few connections, further ones are requested from
+/// just after the latest connection found until the window is covered.
+/// Connections found twice are left to [`dedup_connections`].
+async fn get_routes_within(
+    ctx: &Context,
+    from: &RoutePoint,
+    to: &RoutePoint,
+    time: DateTime<Local>,
+    minutes: u32,
+    config: &GetRoutesConfig,
+) -> Result<Vec<Connection>, mvg_api::Error> {
+    let end = time + chrono::Duration::minutes(minutes.into());
+    let mut routes: Vec<Connection> = Vec::new();
+    let mut start = time;
+    for _ in 0..MAX_WITHIN_REQUESTS {
+        let found =
+            get_routes_with_client(&ctx.client, from, to, Some(start), Some(false), config).await?;
+        let Some(latest) = found.iter().map(|c| c.origin().planned_departure).max() else {
+            break;
+        };
+        routes.extend(found);
+        if latest < start || latest >= end {
+            break;
+        }
+        start = latest + chrono::Duration::minutes(1);
+    }
+    routes.retain(|c| (time..=end).contains(&c.origin().planned_departure));
+    Ok(routes)
+}
+
+/// Prints the stops of each connection leg by leg, marking via stops.
+fn print_stops(routes: &[Connection]) {
+    for (i, connection) in routes.iter().enumerate() {
+        println!(
+            "\n{}. {} - {}",
+            i + 1,
+            connection.origin().planned_departure.format("%H:%M"),
+            connection.destination().planned_departure.format("%H:%M")
+        );
+        for part in &connection.parts {
+            println!(
+                "   {} ➜ {}",
+                line_badge(&part.line.label, &part.line.transport_type, part.line.sev),
+                part.line.destination
+            );
+            let stops = std::iter::once(&part.from)
+                .chain(&part.intermediate_stops)
+                .chain(std::iter::once(&part.to));
+            for stop in stops {
+                let via = if stop.is_via_stop { " (via)" } else { "" };
+                println!(
+                    "     {}  {}{}",
+                    stop.planned_departure.format("%H:%M"),
+                    stop.name,
+                    via
+                );
+            }
+        }
+    }
+}
+
+/// The names of the lines affected by notifications active at `now`.
+fn active_notification_lines(
+    notifications: &[Notification],
+    now: DateTime<Local>,
+) -> HashSet<&str> {
+    notifications
+        .iter()
+        .filter(|n| n.is_active(now))
+        .flat_map(|n| &n.lines)
+        .map(|line| line.name.as_str())
+        .collect()
+}
+
+fn routes_table(
+    routes: &[Connection],
+    args: &RoutesArgs,
+    now: DateTime<Local>,
+    disrupted_lines: &HashSet<&str>,
+) -> Table {
     let table_entries = routes
         .iter()
         .map(|connection| {
-            let origin = &connection.parts[0].from;
-            let destination = &connection.parts[connection.parts.len() - 1].to;
+            let origin = connection.origin();
+            let destination = connection.destination();
             let time = format!(
                 "{} - {}",
                 origin.planned_departure.format("%H:%M"),
                 destination.planned_departure.format("%H:%M")
             );
-            let in_minutes = (origin.planned_departure.time() - Local::now().time())
-                .num_minutes()
-                .to_string();
-            let duration = (destination.planned_departure.time() - origin.planned_departure.time())
-                .num_minutes()
-                .to_string();
-            let lines = connection
-                .parts
-                .iter()
-                .map(|x| colorize_line(&x.line.label))
-                .collect::<Vec<_>>()
-                .join(", ");
+            let in_minutes = (origin.planned_departure - now).num_minutes().to_string();
+            let duration = connection.total_duration().num_minutes().to_string();
+            let lines = if args.compact_lines {
+                compact_line_summary(connection)
+            } else {
+                line_summary(connection)
+            };
             let delay = match origin.departure_delay_in_minutes {
                 Some(delay) if delay != 0 => delay.to_string(),
                 _ => "-".to_string(),
             };
-            let info = connection
+            let mut info = connection
                 .parts
                 .iter()
                 .flat_map(|x| x.messages.clone())
-                .collect::<Vec<_>>()
-                .join("\n");
+                .collect::<Vec<_>>();
+            if !args.no_infos {
+                for part_info in connection.parts.iter().flat_map(|x| &x.infos) {
+                    if !info.contains(part_info) {
+                        info.push(part_info.clone());
+                    }
+                }
+            }
+            for station in connection.stations_with_broken_elevator() {
+                info.insert(0, format!("♿ Elevator out of order at {}", station));
+            }
+            if connection.has_transfer_at_risk() {
+                info.insert(0, "⚠ Transfer at risk".to_string());
+            }
+            for part in &connection.parts {
+                let label = part.line.label.as_str();
+                let warning = format!("⚠ disruption on {}", label);
+                if disrupted_lines.contains(label) && !info.contains(&warning) {
+                    info.insert(0, warning);
+                }
+            }
+            let info = info.join("\n");
+            let distance = connection
+                .parts
+                .iter()
+                .map(|part| part.path_length())
+                .sum::<f64>();
+            let distance = format!("{:.1} km", distance / 1000.0);
+            let zones = zones_display(&connection.ticketing_information);
 
             RouteTableEntry {
                 time,
@@ -196,6 +1116,8 @@ async fn handle_routes(
                 duration,
                 lines,
                 delay,
+                distance,
+                zones,
                 info,
             }
         })
@@ -203,83 +1125,701 @@ async fn handle_routes(
 
     let mut table = Table::new(table_entries);
     table.with(tabled::settings::Style::rounded());
-    let from_name = match name_from_location(from_response) {
-        Some(s) => s,
-        None => anyhow::bail!("No station name found for {}", from),
-    };
-    let to_name = match name_from_location(to_response) {
-        Some(s) => s,
-        None => anyhow::bail!("No station name found for {}", to),
-    };
-    spinner.stop_and_persist("✔", format!("Connections for: {} ➜ {}", from_name, to_name));
-    println!("{}", table);
-
-    Ok(())
+    if !args.show_distance {
+        table.with(Disable::column(ByColumnName::new("Distance")));
+    }
+    table
 }
 
 #[derive(Tabled)]
-struct DeparturesTableEntry {
-    #[tabled(rename = "Time")]
-    time: String,
-    #[tabled(rename = "In")]
-    in_minutes: String,
+struct LegTableEntry {
+    #[tabled(rename = "#")]
+    connection: String,
+    #[tabled(rename = "From")]
+    from: String,
+    #[tabled(rename = "To")]
+    to: String,
     #[tabled(rename = "Line")]
     line: String,
-    #[tabled(rename = "Destination")]
-    destination: String,
+    #[tabled(rename = "Departure")]
+    departure: String,
+    #[tabled(rename = "Arrival")]
+    arrival: String,
     #[tabled(rename = "Delay")]
     delay: String,
-    #[tabled(rename = "Info")]
-    info: String,
+    #[tabled(rename = "Occupancy")]
+    occupancy: String,
 }
 
-async fn handle_departures(station: String, offset: Option<usize>) -> Result<()> {
-    let mut spinner = Spinner::new(Spinners::Aesthetic, "Fetching...".to_string());
-    let station_response = &get_station(&station).await?[0];
-    let station_id = match station_response {
-        mvg_api::Location::Station(s) => &s.global_id,
-        _ => panic!("No station {} found", station),
-    };
-    let offset = offset.unwrap_or(0);
-    let departures = get_departures(station_id, offset).await?;
-    let departures_table_entries = departures.iter().map(|departure| {
-        let time = departure.planned_departure_time.format("%H:%M").to_string();
-        let in_minutes = (departure.planned_departure_time.time() - Local::now().time())
-            .num_minutes()
-            .to_string();
-        let line = colorize_line(&departure.label);
-        let destination = departure.destination.clone();
-        let delay = match departure.delay_in_minutes {
-            Some(min) if min != 0 => min.to_string(),
-            _ => "-".to_string(),
-        };
-        let info = departure.messages.join("\n");
-        DeparturesTableEntry {
-            time,
-            in_minutes,
-            line,
-            destination,
-            delay,
-            info,
+/// A table with one row per leg, numbered by connection and with a line
+/// between connections.
+fn legs_table(routes: &[Connection]) -> Table {
+    let mut separators = Vec::new();
+    let mut entries = Vec::new();
+    for (i, connection) in routes.iter().enumerate() {
+        if i > 0 {
+            separators.push(entries.len() + 1);
         }
-    });
+        for (j, part) in connection.parts.iter().enumerate() {
+            let delay = match part.from.departure_delay_in_minutes {
+                Some(delay) if delay != 0 => delay.to_string(),
+                _ => "-".to_string(),
+            };
+            entries.push(LegTableEntry {
+                connection: if j == 0 {
+                    (i + 1).to_string()
+                } else {
+                    String::new()
+                },
+                from: part.from.name.clone(),
+                to: part.to.name.clone(),
+                line: line_badge(&part.line.label, &part.line.transport_type, part.line.sev),
+                departure: part.from.planned_departure.format("%H:%M").to_string(),
+                arrival: part.to.planned_departure.format("%H:%M").to_string(),
+                delay,
+                occupancy: colorize_occupancy(part.occupancy),
+            });
+        }
+    }
 
-    let station_name = match name_from_location(station_response) {
-        Some(s) => s,
-        None => anyhow::bail!("No station name found for {}", station),
+    let mut table = Table::new(entries);
+    table.with(tabled::settings::Style::rounded());
+    let separator = tabled::settings::Style::rounded().get_horizontal();
+    for row in separators {
+        table.with(HorizontalLine::new(row, separator));
+    }
+    table
+}
+
+/// A connection as printed with `--format json`, with its total duration.
+#[derive(Serialize)]
+struct ConnectionJson<'a> {
+    #[serde(flatten)]
+    connection: &'a Connection,
+    duration: String,
+}
+
+impl<'a> From<&'a Connection> for ConnectionJson<'a> {
+    fn from(connection: &'a Connection) -> Self {
+        ConnectionJson {
+            connection,
+            duration: iso8601_duration(connection.total_duration()),
+        }
+    }
+}
+
+/// Formats a duration in ISO 8601, e.g. "PT1H13M".
+fn iso8601_duration(duration: chrono::Duration) -> String {
+    let sign = if duration < chrono::Duration::zero() {
+        "-"
+    } else {
+        ""
+    };
+    let minutes = duration.num_minutes().abs();
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}PT{}M", sign, m),
+        (h, 0) => format!("{}PT{}H", sign, h),
+        (h, m) => format!("{}PT{}H{}M", sign, h, m),
+    }
+}
+
+/// A compact overview of a single connection for `--summary-json`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TripSummary {
+    departure: DateTime<Local>,
+    arrival: DateTime<Local>,
+    duration: String,
+    duration_in_minutes: i64,
+    changes: usize,
+    first_line: String,
+    last_line: String,
+    delayed: bool,
+    disrupted: bool,
+}
+
+impl From<&Connection> for TripSummary {
+    fn from(connection: &Connection) -> Self {
+        let origin = connection.origin();
+        let destination = connection.destination();
+        TripSummary {
+            departure: origin.planned_departure,
+            arrival: destination.planned_departure,
+            duration: iso8601_duration(connection.total_duration()),
+            duration_in_minutes: connection.total_duration().num_minutes(),
+            changes: connection.num_changes(),
+            first_line: connection.parts[0].line.label.clone(),
+            last_line: connection.parts[connection.parts.len() - 1]
+                .line
+                .label
+                .clone(),
+            delayed: origin.departure_delay_in_minutes.unwrap_or(0) > 0,
+            disrupted: connection.has_transfer_at_risk()
+                || connection
+                    .parts
+                    .iter()
+                    .any(|part| !part.messages.is_empty()),
+        }
+    }
+}
+
+/// A link to public transport directions for the connection in a maps application.
+fn maps_url(connection: &Connection, provider: MapsProvider) -> String {
+    let origin = connection.origin();
+    let destination = connection.destination();
+    let (from, to) = (
+        format!("{},{}", origin.latitude, origin.longitude),
+        format!("{},{}", destination.latitude, destination.longitude),
+    );
+    match provider {
+        MapsProvider::Google => format!(
+            "https://www.google.com/maps/dir/?api=1&origin={}&destination={}&travelmode=transit",
+            from, to
+        ),
+        MapsProvider::Apple => format!(
+            "https://maps.apple.com/?saddr={}&daddr={}&dirflg=r",
+            from, to
+        ),
+        MapsProvider::Osm => format!(
+            "https://www.openstreetmap.org/directions?route={};{}",
+            from, to
+        ),
+    }
+}
+
+/// The fare zones of a connection, e.g. "M, 1", with zone 0 being the
+/// inner zone M. If there are none, the alternative zones are shown
+/// instead, e.g. "alt. 1, 2".
+fn zones_display(ticketing: &TicketingInformation) -> String {
+    let names = |zones: &[usize]| {
+        zones
+            .iter()
+            .map(|&zone| match zone {
+                0 => "M".to_string(),
+                zone => zone.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match (&ticketing.zones[..], &ticketing.alternative_zones[..]) {
+        ([], []) => "-".to_string(),
+        ([], alternatives) => format!("alt. {}", names(alternatives)),
+        (zones, _) => names(zones),
+    }
+}
+
+/// The next connection on one line, e.g. "10:02 (+2)  28 min  U3, U6".
+fn next_connection_line(connection: &Connection) -> String {
+    let origin = connection.origin();
+    let departure = origin.planned_departure.format("%H:%M");
+    let departure = match origin.departure_delay_in_minutes {
+        Some(delay) if delay != 0 => format!("{} ({:+})", departure, delay),
+        _ => departure.to_string(),
     };
+    format!(
+        "{}  {} min  {}",
+        departure,
+        connection.total_duration().num_minutes(),
+        line_summary(connection)
+    )
+}
 
-    spinner.stop_and_persist("✔", format!("Departures for: {}", station_name));
+fn line_summary(connection: &Connection) -> String {
+    connection
+        .parts
+        .iter()
+        .map(|x| line_badge(&x.line.label, &x.line.transport_type, x.line.sev))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn compact_line_summary(connection: &Connection) -> String {
+    let first = &connection.parts[0].line;
+    let last = &connection.parts[connection.parts.len() - 1].line;
+    let first = line_badge(&first.label, &first.transport_type, first.sev);
+    let last = line_badge(&last.label, &last.transport_type, last.sev);
+    match connection.parts.len() - 1 {
+        0 => first,
+        1 => format!("{} … {} (1 change)", first, last),
+        changes => format!("{} … {} ({} changes)", first, last, changes),
+    }
+}
+
+#[derive(Tabled)]
+struct DeparturesTableEntry {
+    #[tabled(rename = "Time")]
+    time: String,
+    #[tabled(rename = "In")]
+    in_minutes: String,
+    #[tabled(rename = "Line")]
+    line: String,
+    #[tabled(rename = "Destination")]
+    destination: String,
+    #[tabled(rename = "Platform")]
+    platform: String,
+    #[tabled(rename = "Delay")]
+    delay: String,
+    #[tabled(rename = "Occupancy")]
+    occupancy: String,
+    #[tabled(rename = "Info")]
+    info: String,
+}
+
+async fn handle_departures(args: DeparturesArgs, ctx: &Context) -> Result<()> {
+    if args.board && ctx.format != OutputFormat::Table {
+        anyhow::bail!("--board can only be shown as a table, not with --format or --json");
+    }
+    let station_response = &match (&args.station, &args.place) {
+        (Some(station), Some(place)) => {
+            select_by_place(find_locations(ctx, station).await?, station, place)?
+        }
+        (Some(station), None) => choose_station(find_locations(ctx, station).await?, station)?,
+        (None, _) => pick_station(ctx).await?,
+    };
+    let Some(station_id) = station_response.id() else {
+        anyhow::bail!(Failure::UnknownStation(format!(
+            "No station {} found",
+            args.station.as_deref().unwrap_or_default()
+        )));
+    };
+    let offset = args.offset.unwrap_or(0);
+    if args.board {
+        return show_board(station_response, station_id, offset, &args, ctx).await;
+    }
+    let interactive = args.interactive
+        && ctx.format == OutputFormat::Table
+        && std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal();
+    if interactive {
+        return show_interactive(station_response, station_id, offset, &args, ctx).await;
+    }
+    if args.watch && ctx.format == OutputFormat::Table {
+        return show_watch(station_response, station_id, offset, &args, ctx).await;
+    }
+    let spinner = start_spinner(ctx.format);
+    let mut departures = fetch_departures(&ctx.client, station_id, offset, &args).await?;
+    ensure_found(&departures, "departure")?;
+    if args.count_by_line {
+        let station_name = status_name(station_response, args.print_ids);
+        return print_line_counts(&departures, spinner, &station_name, ctx.format);
+    }
+    if ctx.format.is_json() {
+        return print_json(&departures, ctx.format);
+    }
+    if ctx.format != OutputFormat::Table {
+        let table = departures_table(&departures, &args, ctx.now(), None);
+        return print_table(&table, ctx.format);
+    }
+
+    let station_name = status_name(station_response, args.print_ids);
+
+    stop_spinner(spinner, format!("Departures for: {}", station_name));
+    let has_several_stops = departures
+        .windows(2)
+        .any(|d| d[0].stop_point_global_id != d[1].stop_point_global_id);
+    if args.group_stops && has_several_stops {
+        departures.sort_by(|a, b| a.stop_point_global_id.cmp(&b.stop_point_global_id));
+        for group in departures.chunk_by(|a, b| a.stop_point_global_id == b.stop_point_global_id) {
+            println!("{}", stop_label(&group[0]));
+            println!("{}", departures_table(group, &args, ctx.now(), None));
+        }
+    } else {
+        println!("{}", departures_table(&departures, &args, ctx.now(), None));
+    }
+
+    Ok(())
+}
+
+#[derive(Tabled)]
+struct LineCountTableEntry {
+    #[tabled(rename = "Line")]
+    line: String,
+    #[tabled(rename = "Departures")]
+    count: usize,
+}
+
+/// Prints how many of the departures each line has, most frequent first.
+fn print_line_counts(
+    departures: &[Departure],
+    spinner: Option<Spinner>,
+    station_name: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for departure in departures {
+        *counts.entry(&departure.label).or_default() += 1;
+    }
+    if format.is_json() {
+        return print_json(&counts, format);
+    }
+
+    let transport_types = departures
+        .iter()
+        .map(|d| (d.label.as_str(), &d.transport_type))
+        .collect::<HashMap<_, _>>();
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by_key(|&(_, count)| Reverse(count));
+    let entries = counts.into_iter().map(|(line, count)| LineCountTableEntry {
+        line: colorize_line(line, transport_types[line]),
+        count,
+    });
+    stop_spinner(spinner, format!("Departures by line for: {}", station_name));
+    let mut table = Table::new(entries);
+    table.with(tabled::settings::Style::rounded());
+    print_table(&table, format)
+}
+
+/// Names the stop of a departure by its platform, or its stop point id if
+/// it has none.
+fn stop_label(departure: &Departure) -> String {
+    let label = departure
+        .platform_display()
+        .unwrap_or_else(|| departure.stop_point_global_id.clone());
+    paint(Style::new().bold(), &label)
+}
+
+/// How many departures are fetched for `--limit 0` and when filters may drop
+/// some of them.
+const MAX_DEPARTURES_FETCHED: usize = 100;
+
+/// Fetches at most `--limit` departures, or all with 0, keeping only delayed
+/// ones with `--delay-threshold`, dropping cancelled ones with
+/// `--hide-cancelled` and full ones with `--max-occupancy`, sorted by
+/// `--sort`, where time means realtime with `--interleave-realtime`.
+async fn fetch_departures(
+    client: &Client,
+    station_id: &str,
+    offset: usize,
+    args: &DeparturesArgs,
+) -> Result<Vec<Departure>> {
+    let filtered =
+        args.delay_threshold.is_some() || args.hide_cancelled || args.max_occupancy.is_some();
+    let limit = if args.limit == 0 || filtered {
+        args.limit.max(MAX_DEPARTURES_FETCHED)
+    } else {
+        args.limit
+    };
+    let mut departures = get_departures_with_client(client, station_id, offset, limit).await?;
+    if let Some(threshold) = args.delay_threshold {
+        departures.retain(|d| d.cancelled || d.delay_in_minutes.unwrap_or(0) >= threshold);
+    }
+    if args.hide_cancelled {
+        departures.retain(|d| !d.cancelled);
+    }
+    if let Some(max_occupancy) = args.max_occupancy {
+        departures.retain(|d| d.occupancy <= max_occupancy);
+    }
+    if args.limit > 0 {
+        departures.truncate(args.limit);
+    }
+    match args.sort {
+        DepartureSort::Time if args.interleave_realtime => {
+            departures.sort_by_key(|d| d.effective_departure())
+        }
+        DepartureSort::Time => departures.sort_by_key(|d| d.planned_departure_time),
+        DepartureSort::Line => departures.sort_by(|a, b| {
+            (&a.label, a.planned_departure_time).cmp(&(&b.label, b.planned_departure_time))
+        }),
+        DepartureSort::Destination => departures.sort_by(|a, b| {
+            (&a.destination, a.planned_departure_time)
+                .cmp(&(&b.destination, b.planned_departure_time))
+        }),
+    }
+    Ok(departures)
+}
+
+/// Identifies a departure across refreshes.
+type DepartureKey = (String, String, DateTime<Local>);
+
+/// The delays in minutes of the previous refresh, for `--since-last`.
+type PreviousDelays = HashMap<DepartureKey, isize>;
+
+fn departure_key(departure: &Departure) -> DepartureKey {
+    (
+        departure.label.clone(),
+        departure.destination.clone(),
+        departure.planned_departure_time,
+    )
+}
+
+fn previous_delays(departures: &[Departure]) -> PreviousDelays {
+    departures
+        .iter()
+        .map(|d| (departure_key(d), d.delay_in_minutes.unwrap_or(0)))
+        .collect()
+}
+
+/// An arrow showing whether the delay of `departure` grew (↑), shrank (↓)
+/// or stayed the same (→) since the previous refresh.
+fn delay_trend(departure: &Departure, previous: &PreviousDelays) -> Option<&'static str> {
+    let before = *previous.get(&departure_key(departure))?;
+    let delay = departure.delay_in_minutes.unwrap_or(0);
+    Some(match delay.cmp(&before) {
+        Ordering::Greater => "↑",
+        Ordering::Less => "↓",
+        Ordering::Equal => "→",
+    })
+}
+
+fn departures_table(
+    departures: &[Departure],
+    args: &DeparturesArgs,
+    now: DateTime<Local>,
+    previous: Option<&PreviousDelays>,
+) -> Table {
+    let departures_table_entries = departures.iter().map(|departure| {
+        let planned = departure.planned_departure_time;
+        let time = if args.realtime {
+            let effective = departure.effective_departure().format("%H:%M");
+            match departure.delay_in_minutes {
+                _ if !departure.realtime => format!("{} (scheduled)", effective),
+                Some(min) if min != 0 => format!("{} ({:+})", effective, min),
+                _ => effective.to_string(),
+            }
+        } else if args.interleave_realtime && departure.effective_departure() != planned {
+            format!(
+                "{} ({})",
+                planned.format("%H:%M"),
+                departure.effective_departure().format("%H:%M")
+            )
+        } else {
+            planned.format("%H:%M").to_string()
+        };
+        let in_minutes = if departure.cancelled {
+            "-".to_string()
+        } else {
+            (departure.effective_departure() - now)
+                .num_minutes()
+                .to_string()
+        };
+        let line = line_badge(&departure.label, &departure.transport_type, departure.sev);
+        let destination = departure.destination.clone();
+        let delay = match departure.delay_in_minutes {
+            _ if departure.cancelled => "-".to_string(),
+            Some(min) if min != 0 => min.to_string(),
+            _ => "-".to_string(),
+        };
+        let delay = match previous.and_then(|p| delay_trend(departure, p)) {
+            Some(trend) => format!("{} {}", delay, trend),
+            None => delay,
+        };
+        let platform = match departure.platform_display() {
+            Some(p) if departure.platform_changed => paint(Fixed(196), &p),
+            Some(p) => p,
+            None => "-".to_string(),
+        };
+        let occupancy = colorize_occupancy(departure.occupancy);
+        let mut info = departure.messages.clone();
+        if departure.cancelled {
+            info.insert(0, paint(Fixed(196).bold(), "CANCELLED"));
+        }
+        let info = info.join("\n");
+        DeparturesTableEntry {
+            time,
+            in_minutes,
+            line,
+            destination,
+            platform,
+            delay,
+            occupancy,
+            info,
+        }
+    });
 
     let mut table = Table::new(departures_table_entries);
     table.with(tabled::settings::Style::rounded());
-    println!("{}", table);
+    table
+}
+
+/// Shows the departures table and fetches it again whenever space or enter
+/// is pressed, until 'q' is pressed.
+async fn show_interactive(
+    station: &Location,
+    station_id: &str,
+    offset: usize,
+    args: &DeparturesArgs,
+    ctx: &Context,
+) -> Result<()> {
+    let station_name = status_name(station, args.print_ids);
+    let mut previous = PreviousDelays::new();
+    loop {
+        let departures = fetch_departures(&ctx.client, station_id, offset, args).await?;
+        let trends = args.since_last.then_some(&previous);
+        print!("\x1b[2J\x1b[H");
+        println!("Departures for: {}", station_name);
+        println!("{}", departures_table(&departures, args, ctx.now(), trends));
+        println!("[space/enter] refresh  [q] quit");
+        previous = previous_delays(&departures);
+        if !wait_for_refresh()? {
+            return Ok(());
+        }
+    }
+}
+
+/// Blocks until a key is pressed and returns whether it asks for a refresh
+/// rather than to quit.
+fn wait_for_refresh() -> Result<bool> {
+    terminal::enable_raw_mode()?;
+    let refresh = loop {
+        let event = match event::read() {
+            Ok(event) => event,
+            Err(e) => {
+                terminal::disable_raw_mode()?;
+                return Err(e.into());
+            }
+        };
+        let Event::Key(key) = event else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char(' ') | KeyCode::Enter => break true,
+            KeyCode::Char('q') | KeyCode::Esc => break false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break false,
+            _ => {}
+        }
+    };
+    terminal::disable_raw_mode()?;
+    Ok(refresh)
+}
 
+/// Shows the departures table and redraws it every `--interval` seconds
+/// with the cursor hidden, until Ctrl-C is pressed. Only a failure of the
+/// first fetch is an error, later ones are shown below the table.
+async fn show_watch(
+    station: &Location,
+    station_id: &str,
+    offset: usize,
+    args: &DeparturesArgs,
+    ctx: &Context,
+) -> Result<()> {
+    let station_name = status_name(station, args.print_ids);
+    let spinner = start_spinner(ctx.format);
+    let departures = fetch_departures(&ctx.client, station_id, offset, args).await?;
+    stop_spinner(spinner, format!("Departures for: {}", station_name));
+    print!("\x1b[?25l");
+    tokio::select! {
+        _ = watch_departures(departures, &station_name, station_id, offset, args, ctx) => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    println!("\x1b[?25h");
     Ok(())
 }
 
+async fn watch_departures(
+    mut departures: Vec<Departure>,
+    station_name: &str,
+    station_id: &str,
+    offset: usize,
+    args: &DeparturesArgs,
+    ctx: &Context,
+) {
+    let mut previous = PreviousDelays::new();
+    let mut warning = None;
+    loop {
+        let trends = args.since_last.then_some(&previous);
+        print!("\x1b[2J\x1b[H");
+        println!("Departures for: {}", station_name);
+        println!("{}", departures_table(&departures, args, ctx.now(), trends));
+        if let Some(e) = warning.take() {
+            println!("⚠ Refresh failed, showing the last departures: {}", e);
+        }
+        tokio::time::sleep(Duration::from_secs(args.interval)).await;
+        match fetch_departures(&ctx.client, station_id, offset, args).await {
+            Ok(refreshed) => {
+                previous = previous_delays(&departures);
+                departures = refreshed;
+            }
+            Err(e) => warning = Some(e),
+        }
+    }
+}
+
+/// Shows the departure board and redraws it every `--interval` seconds.
+/// Only a failure of the first fetch is an error, later ones are shown below
+/// the board.
+async fn show_board(
+    station: &Location,
+    station_id: &str,
+    offset: usize,
+    args: &DeparturesArgs,
+    ctx: &Context,
+) -> Result<()> {
+    let station_name = name_from_location(station);
+    let mut departures = fetch_departures(&ctx.client, station_id, offset, args).await?;
+    let mut previous = PreviousDelays::new();
+    let mut warning = None;
+    loop {
+        let width = ctx.width();
+        let trends = args.since_last.then_some(&previous);
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "{}",
+            render_board(&station_name, &departures, width, ctx.now(), trends)
+        );
+        if let Some(e) = warning.take() {
+            println!("⚠ Refresh failed, showing the last departures: {}", e);
+        }
+        tokio::time::sleep(Duration::from_secs(args.interval)).await;
+        match fetch_departures(&ctx.client, station_id, offset, args).await {
+            Ok(refreshed) => {
+                previous = previous_delays(&departures);
+                departures = refreshed;
+            }
+            Err(e) => warning = Some(e),
+        }
+    }
+}
+
+fn render_board(
+    station_name: &str,
+    departures: &[Departure],
+    width: usize,
+    now: DateTime<Local>,
+    previous: Option<&PreviousDelays>,
+) -> String {
+    let row = |left: String, right: String| {
+        let padding = width.saturating_sub(string_width(&left) + string_width(&right) + 2);
+        format!(" {}{}{} ", left, " ".repeat(padding.max(1)), right)
+    };
+    let bold = Style::new().bold();
+
+    let mut lines = vec![
+        row(station_name.to_string(), now.format("%H:%M").to_string()),
+        "─".repeat(width),
+    ];
+    for departure in departures {
+        let countdown = match (departure.effective_departure() - now).num_minutes() {
+            _ if departure.cancelled => "cancelled".to_string(),
+            m if m <= 0 => "now".to_string(),
+            m => format!("{} min", m),
+        };
+        let countdown = match previous.and_then(|p| delay_trend(departure, p)) {
+            Some(trend) => format!(
+                "{:+} {}  {}",
+                departure.delay_in_minutes.unwrap_or(0),
+                trend,
+                countdown
+            ),
+            None => countdown,
+        };
+        let left = format!(
+            "{}  {}",
+            line_badge(&departure.label, &departure.transport_type, departure.sev),
+            paint(bold, &departure.destination)
+        );
+        lines.push(String::new());
+        lines.push(row(left, paint(bold, &countdown)));
+    }
+    lines.join("\n")
+}
+
 #[derive(Tabled)]
 struct NotificationsTableEntry {
+    #[tabled(rename = "#")]
+    index: usize,
     #[tabled(rename = "Lines")]
     lines: String,
     #[tabled(rename = "Duration")]
@@ -288,63 +1828,509 @@ struct NotificationsTableEntry {
     details: String,
 }
 
-async fn handle_notifications(filter: Option<String>) -> Result<()> {
-    let notifications = get_notifications().await?;
-    let notifications_table_entries = notifications
-        .iter()
-        .map(|notification| {
-            let lines = notification
-                .lines
-                .iter()
-                .map(|line| colorize_line(&line.name))
-                .collect::<Vec<_>>()
-                .join(", ");
-            let duration_from = notification.active_duration.from_date.format("%d.%m.%Y");
-            let duration_to = notification
-                .active_duration
-                .to_date
-                .map(|x| x.format("%d.%m.%Y").to_string())
-                .unwrap_or("".to_string());
-            let duration = format!("{} - {}", duration_from, duration_to);
-            let title = html2text::from_read(notification.title.as_bytes(), 99999);
-            let text = html2text::from_read(notification.text.as_bytes(), 99999);
-            let details = format!("{}\n{}", Style::new().bold().paint(title), text);
-            NotificationsTableEntry {
-                lines,
-                duration,
-                details,
-            }
-        })
-        .collect::<Vec<_>>();
+async fn handle_notifications(args: NotificationsArgs, ctx: &Context) -> Result<()> {
+    let station_id = match &args.station {
+        Some(station) => {
+            let location = resolve_location(ctx, station, LocationMode::Station, None).await?;
+            let Some(id) = location.id() else {
+                anyhow::bail!(Failure::UnknownStation(format!(
+                    "No station {} found",
+                    station
+                )));
+            };
+            Some(id.to_string())
+        }
+        None => None,
+    };
+    let station_id = station_id.as_deref();
+    if let Some(interval) = args.watch {
+        return watch_notifications(&args, station_id, Duration::from_secs(interval), ctx).await;
+    }
+    let max = args.max();
+    let mut notifications = get_notifications_with_client(&ctx.client).await?;
+    if max.is_some() {
+        notifications.sort_by(|a, b| {
+            b.active_duration
+                .from_date
+                .cmp(&a.active_duration.from_date)
+        });
+    }
 
-    let notifications_table_entries = match filter {
-        Some(f) => notifications_table_entries
-            .into_iter()
-            .filter(|entry| entry.lines.to_lowercase().contains(&f.to_lowercase()))
-            .collect::<Vec<_>>(),
-        _ => notifications_table_entries,
+    filter_notifications(&mut notifications, &args, station_id, ctx.now());
+
+    if let Some(action) = args.action {
+        return handle_notification_action(&notifications, action, ctx.width());
+    }
+
+    let hidden = match max {
+        Some(n) if notifications.len() > n => {
+            let hidden = notifications.len() - n;
+            notifications.truncate(n);
+            hidden
+        }
+        _ => 0,
     };
 
-    if notifications_table_entries.is_empty() {
+    let indexed = notifications.iter().enumerate().collect::<Vec<_>>();
+    let groups = if args.by_line {
+        group_by_line(&indexed)
+    } else {
+        vec![(None, indexed)]
+    };
+
+    if ctx.format != OutputFormat::Table {
+        if args.condensed {
+            let condensed = condense(&groups[0].1, args.similarity)
+                .into_iter()
+                .map(|cluster| cluster.into_iter().map(|(_, n)| n).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            return print_json(&condensed, ctx.format);
+        }
+        if args.by_line {
+            let by_line = groups
+                .iter()
+                .map(|(line, group)| {
+                    let line = line.as_deref().unwrap_or_default();
+                    (line, group.iter().map(|(_, n)| n).collect())
+                })
+                .collect::<BTreeMap<_, Vec<_>>>();
+            return print_json(&by_line, ctx.format);
+        }
+        return print_json(&notifications, ctx.format);
+    }
+
+    if notifications.is_empty() {
         println!("No notifications found");
         return Ok(());
     };
 
-    let (TerminalWidth(terminal_width), _) = terminal_size().expect("Not in a terminal");
-    let mut table = Table::new(notifications_table_entries);
-    table
-        .with(tabled::settings::Style::rounded())
-        .with(Modify::new(Columns::first()).with(Width::wrap(10).keep_words()))
-        .with(
-            Modify::new(Columns::last())
-                .with(Width::wrap(terminal_width as usize - 50).keep_words()),
-        );
+    let width = ctx.width();
+    for (line, group) in groups {
+        if let Some(line) = line {
+            let badge = match group[0].1.lines.iter().find(|l| l.name == line) {
+                Some(l) => line_badge(&l.name, &l.type_of_transport, l.sev),
+                None => colorize_line(&line, &TransportType::default()),
+            };
+            println!("{}", badge);
+        }
+        let clusters = if args.condensed {
+            condense(&group, args.similarity)
+        } else {
+            group.into_iter().map(|n| vec![n]).collect()
+        };
+        let notifications_table_entries = clusters
+            .iter()
+            .map(|cluster| notifications_table_entry(cluster))
+            .collect::<Vec<_>>();
+
+        let mut table = Table::new(notifications_table_entries);
+        table
+            .with(tabled::settings::Style::rounded())
+            .with(Modify::new(Columns::single(1)).with(Width::wrap(10).keep_words()))
+            .with(
+                Modify::new(Columns::last())
+                    .with(Width::wrap(width.saturating_sub(55).max(10)).keep_words()),
+            );
+
+        println!("{}", table);
+    }
+    if hidden > 0 {
+        println!("... and {} more (use --all)", hidden);
+    }
+
+    Ok(())
+}
+
+/// Polls the notifications every `interval` and prints those not seen
+/// before. A failed poll is skipped.
+async fn watch_notifications(
+    args: &NotificationsArgs,
+    station_id: Option<&str>,
+    interval: Duration,
+    ctx: &Context,
+) -> Result<()> {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut first_poll = true;
+    loop {
+        match get_notifications_with_client(&ctx.client).await {
+            Ok(mut notifications) => {
+                filter_notifications(&mut notifications, args, station_id, ctx.now());
+                if first_poll {
+                    println!(
+                        "Watching {} notifications for new ones...",
+                        notifications.len()
+                    );
+                }
+                for notification in notifications {
+                    if !seen.insert(notification.id.clone()) || first_poll {
+                        continue;
+                    }
+                    println!();
+                    print_notification(&notification, ctx.width());
+                    #[cfg(feature = "desktop-notifications")]
+                    if args.desktop {
+                        show_desktop_notification(&notification);
+                    }
+                }
+                first_poll = false;
+            }
+            Err(e) => eprintln!("Fetching notifications failed, retrying later: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(feature = "desktop-notifications")]
+fn show_desktop_notification(notification: &Notification) {
+    let title = html2text::from_read(notification.title.as_bytes(), 99999);
+    let lines = notification
+        .lines
+        .iter()
+        .map(|line| line.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Err(e) = notify_rust::Notification::new()
+        .summary(title.trim())
+        .body(&lines)
+        .show()
+    {
+        eprintln!("Showing desktop notification failed: {}", e);
+    }
+}
+
+/// Keeps the notifications matching all of `--filter`, `--active`,
+/// `--upcoming`, `--provider`, `--type` and `--event-type` and, given the
+/// global id of the `--station`, those affecting that station.
+fn filter_notifications(
+    notifications: &mut Vec<Notification>,
+    args: &NotificationsArgs,
+    station_id: Option<&str>,
+    now: DateTime<Local>,
+) {
+    if args.active {
+        notifications.retain(|n| n.is_active(now));
+    }
+    if args.upcoming {
+        notifications.retain(|n| n.is_upcoming(now));
+    }
+    if let Some(provider) = &args.provider {
+        notifications.retain(|n| n.provider.eq_ignore_ascii_case(provider));
+    }
+    if let Some(type_name) = &args.type_name {
+        notifications.retain(|n| n.type_name.eq_ignore_ascii_case(type_name));
+    }
+    if let Some(event_type) = &args.event_type {
+        notifications.retain(|n| {
+            n.event_types
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(event_type))
+        });
+    }
+    if let Some(f) = &args.filter {
+        filter_by_line(notifications, f);
+    }
+    if let Some(station_id) = station_id {
+        filter_by_station(notifications, station_id);
+    }
+}
+
+fn filter_by_line(notifications: &mut Vec<Notification>, filter: &str) {
+    let filter = filter.to_lowercase();
+    notifications.retain(|notification| {
+        notification
+            .lines
+            .iter()
+            .any(|line| line.name.to_lowercase().contains(&filter))
+    });
+}
+
+/// Keeps the notifications listing the station, or one of its stops, under
+/// any of their lines.
+fn filter_by_station(notifications: &mut Vec<Notification>, global_id: &str) {
+    let stop_prefix = format!("{}:", global_id);
+    notifications.retain(|notification| {
+        notification
+            .lines
+            .iter()
+            .flat_map(|line| &line.stations)
+            .any(|station| station.id == global_id || station.id.starts_with(&stop_prefix))
+    });
+}
+
+type IndexedNotifications<'a> = Vec<(usize, &'a Notification)>;
+
+/// A table row for one notification, or for several with the same content,
+/// listing the lines and stations of all of them.
+fn notifications_table_entry(cluster: &[(usize, &Notification)]) -> NotificationsTableEntry {
+    let (i, notification) = cluster[0];
+    let mut lines: Vec<&NotificationLines> = Vec::new();
+    let mut stations: Vec<&str> = Vec::new();
+    for line in cluster.iter().flat_map(|(_, n)| &n.lines) {
+        if !lines.iter().any(|l| l.name == line.name) {
+            lines.push(line);
+        }
+        for station in &line.stations {
+            if !stations.contains(&station.name.as_str()) {
+                stations.push(&station.name);
+            }
+        }
+    }
+    let lines = lines
+        .into_iter()
+        .map(|line| line_badge(&line.name, &line.type_of_transport, line.sev))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let duration = notification_duration(notification);
+    let title = html2text::from_read(notification.title.as_bytes(), 99999);
+    let text = html2text::from_read(notification.text.as_bytes(), 99999);
+    let mut details = format!("{}\n{}", paint(Style::new().bold(), &title), text);
+    if cluster.len() > 1 && !stations.is_empty() {
+        details.push_str(&format!("\nStations: {}", stations.join(", ")));
+    }
+    NotificationsTableEntry {
+        index: i + 1,
+        lines,
+        duration,
+        details,
+    }
+}
+
+/// Groups notifications with the same title and text, ignoring case,
+/// whitespace and markup. With a `similarity` between 0 and 1, notifications
+/// sharing at least that ratio of words are grouped as well.
+fn condense<'a>(
+    notifications: &IndexedNotifications<'a>,
+    similarity: Option<f64>,
+) -> Vec<IndexedNotifications<'a>> {
+    let mut clusters: Vec<(HashSet<String>, IndexedNotifications)> = Vec::new();
+    let mut by_content: HashMap<String, usize> = HashMap::new();
+    for &(i, notification) in notifications {
+        let content = normalize_notification(notification);
+        let words = content
+            .split(' ')
+            .map(str::to_string)
+            .collect::<HashSet<_>>();
+        let cluster = by_content.get(&content).copied().or_else(|| {
+            let threshold = similarity?;
+            clusters
+                .iter()
+                .position(|(other, _)| word_similarity(&words, other) >= threshold)
+        });
+        match cluster {
+            Some(c) => clusters[c].1.push((i, notification)),
+            None => {
+                by_content.insert(content, clusters.len());
+                clusters.push((words, vec![(i, notification)]));
+            }
+        }
+    }
+    clusters.into_iter().map(|(_, cluster)| cluster).collect()
+}
+
+fn normalize_notification(notification: &Notification) -> String {
+    let title = html2text::from_read(notification.title.as_bytes(), 99999);
+    let text = html2text::from_read(notification.text.as_bytes(), 99999);
+    format!("{} {}", title, text)
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
+/// The Jaccard similarity of two sets of words.
+fn word_similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Groups notifications under each line they affect, so a notification for
+/// several lines appears in each of their groups, but only once per group.
+fn group_by_line<'a>(
+    notifications: &IndexedNotifications<'a>,
+) -> Vec<(Option<String>, IndexedNotifications<'a>)> {
+    let mut groups: BTreeMap<String, IndexedNotifications> = BTreeMap::new();
+    for &(i, notification) in notifications {
+        for line in &notification.lines {
+            let group = groups.entry(line.name.clone()).or_default();
+            if !group.iter().any(|(j, _)| *j == i) {
+                group.push((i, notification));
+            }
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(line, group)| (Some(line), group))
+        .collect()
+}
+
+fn notification_lines(notification: &Notification) -> String {
+    notification
+        .lines
+        .iter()
+        .map(|line| line_badge(&line.name, &line.type_of_transport, line.sev))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn notification_duration(notification: &Notification) -> String {
+    let duration_from = notification.active_duration.from_date.format("%d.%m.%Y");
+    let duration_to = notification
+        .active_duration
+        .to_date
+        .map(|x| x.format("%d.%m.%Y").to_string())
+        .unwrap_or("".to_string());
+    format!("{} - {}", duration_from, duration_to)
+}
+
+fn handle_notification_action(
+    notifications: &[Notification],
+    action: NotificationsAction,
+    width: usize,
+) -> Result<()> {
+    let index = match action {
+        NotificationsAction::Show { index } | NotificationsAction::Open { index, .. } => index,
+    };
+    let notification = match index.checked_sub(1).and_then(|i| notifications.get(i)) {
+        Some(n) => n,
+        None => anyhow::bail!("No notification with number {}", index),
+    };
+
+    match action {
+        NotificationsAction::Show { .. } => print_notification(notification, width),
+        NotificationsAction::Open { link, .. } => {
+            let links = &notification.links;
+            let link = match (link, links.len()) {
+                (_, 0) => anyhow::bail!("Notification {} has no links", index),
+                (Some(l), _) => l,
+                (None, 1) => 1,
+                (None, _) => prompt_link(links)?,
+            };
+            match link.checked_sub(1).and_then(|i| links.get(i)) {
+                Some(l) => open::that(&l.href)?,
+                None => anyhow::bail!("No link with number {}", link),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_notification(notification: &Notification, width: usize) {
+    let title = html2text::from_read(notification.title.as_bytes(), width);
+    let text = html2text::from_read(notification.text.as_bytes(), width);
+    println!("{}", paint(Style::new().bold(), title.trim_end()));
+    println!("{}", notification_lines(notification));
+    println!("{}\n", notification_duration(notification));
+    println!("{}", text.trim_end());
+    for (i, link) in notification.links.iter().enumerate() {
+        println!("[{}] {}: {}", i + 1, link.name, link.href);
+    }
+}
+
+fn prompt_link(links: &[NotificationLink]) -> Result<usize> {
+    for (i, link) in links.iter().enumerate() {
+        println!("[{}] {}", i + 1, link.name);
+    }
+    print!("Open which link? ");
+    std::io::stdout().flush()?;
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    Ok(input.trim().parse()?)
+}
+
+#[derive(Tabled)]
+struct StationsTableEntry {
+    #[tabled(rename = "Type")]
+    type_name: String,
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Place")]
+    place: String,
+    #[tabled(rename = "Global id")]
+    global_id: String,
+}
+
+async fn handle_stations(query: &str, ctx: &Context) -> Result<()> {
+    let spinner = start_spinner(ctx.format);
+    let locations = find_locations(ctx, query).await?;
+    if ctx.format != OutputFormat::Table {
+        return print_json(&locations, ctx.format);
+    }
+    let entries = locations.iter().map(|location| {
+        let type_name = match location {
+            Location::Station(_) => "Station",
+            Location::Address(_) => "Address",
+            Location::Poi(_) => "POI",
+        };
+        StationsTableEntry {
+            type_name: type_name.to_string(),
+            name: location.name().to_string(),
+            place: location.place().to_string(),
+            global_id: location.id().unwrap_or("-").to_string(),
+        }
+    });
+
+    stop_spinner(spinner, format!("Locations for: {}", query));
+    let mut table = Table::new(entries);
+    table.with(tabled::settings::Style::rounded());
     println!("{}", table);
 
     Ok(())
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ElevatorStatus {
+    elevator_out_of_order: bool,
+    escalator_out_of_order: bool,
+}
+
+async fn handle_elevator(query: &str, place: Option<&str>, ctx: &Context) -> Result<()> {
+    let station = resolve_location(ctx, query, LocationMode::Station, place).await?;
+    let spinner = start_spinner(ctx.format);
+    let Some(global_id) = station.id() else {
+        anyhow::bail!(Failure::UnknownStation(format!(
+            "No station {} found",
+            query
+        )));
+    };
+    let detail = match get_station_detail_with_client(&ctx.client, global_id).await {
+        Ok(detail) => detail,
+        Err(MvgError::NoStation(_)) => anyhow::bail!(Failure::NothingFound(format!(
+            "No elevator status found for {}",
+            query
+        ))),
+        Err(e) => return Err(e.into()),
+    };
+    let status = ElevatorStatus {
+        elevator_out_of_order: detail.has_out_of_order_elevator,
+        escalator_out_of_order: detail.has_out_of_order_escalator,
+    };
+    if ctx.format != OutputFormat::Table {
+        return print_json(&status, ctx.format);
+    }
+
+    stop_spinner(
+        spinner,
+        format!("Elevators at: {}", name_from_location(&station)),
+    );
+    if status.elevator_out_of_order {
+        println!("⚠ elevator out of order");
+    } else {
+        println!("✓ elevators OK");
+    }
+    if status.escalator_out_of_order {
+        println!("⚠ escalator out of order");
+    } else {
+        println!("✓ escalators OK");
+    }
+
+    Ok(())
+}
+
 fn handle_map(region: bool, tram: bool, night: bool) -> Result<()> {
     if let (false, false, false) = (region, tram, night) {
         open::that(
@@ -369,16 +2355,817 @@ fn handle_map(region: bool, tram: bool, night: bool) -> Result<()> {
     Ok(())
 }
 
-fn name_from_location(location_response: &Location) -> Option<String> {
-    match location_response {
-        mvg_api::Location::Station(s) => {
-            let a = nu_ansi_term::Style::new().bold().paint(&s.name).to_string();
-            let b = nu_ansi_term::Style::new()
-                .italic()
-                .paint(&s.place)
-                .to_string();
-            Some([a, b].join(", "))
+/// Opens the plan of the surroundings of `query` in the browser.
+async fn open_surrounding_plan(query: &str, place: Option<&str>, ctx: &Context) -> Result<()> {
+    let location = resolve_location(ctx, query, LocationMode::Station, place).await?;
+    let Location::Station(station) = &location else {
+        anyhow::bail!(Failure::UnknownStation(format!(
+            "No station {} found",
+            query
+        )));
+    };
+    if station.surrounding_plan_link.is_empty() {
+        anyhow::bail!(
+            "The MVG has no plan of the surroundings of {}, {}; `mvg map` shows the network plans instead",
+            station.name,
+            station.place
+        );
+    }
+    open::that(&station.surrounding_plan_link)?;
+    Ok(())
+}
+
+fn start_spinner(format: OutputFormat) -> Option<Spinner> {
+    (format == OutputFormat::Table)
+        .then(|| Spinner::new(Spinners::Aesthetic, "Fetching...".to_string()))
+}
+
+fn stop_spinner(spinner: Option<Spinner>, message: String) {
+    if let Some(mut spinner) = spinner {
+        spinner.stop_and_persist("✔", message);
+    }
+}
+
+fn print_json<T: Serialize>(value: &T, format: OutputFormat) -> Result<()> {
+    if format.delimiter().is_some() {
+        anyhow::bail!("CSV and TSV output are only supported by routes and departures");
+    }
+    let json = match format {
+        OutputFormat::JsonCompact => serde_json::to_string(value)?,
+        _ => serde_json::to_string_pretty(value)?,
+    };
+    println!("{}", json);
+    Ok(())
+}
+
+/// Prints a table as is or, for CSV and TSV, as delimited rows of plain text
+/// with the column names as header.
+fn print_table(table: &Table, format: OutputFormat) -> Result<()> {
+    match format.delimiter() {
+        Some(delimiter) => write_delimited(table, delimiter, std::io::stdout()),
+        None => {
+            println!("{}", table);
+            Ok(())
+        }
+    }
+}
+
+/// Writes the rows of a table separated by `delimiter`, with the cells
+/// stripped of colors and padding and occupancy bars written as their level,
+/// e.g. "LOW".
+fn write_delimited(table: &Table, delimiter: u8, writer: impl Write) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(writer);
+    let mut occupancy_column = None;
+    for (i, row) in table.get_records().iter().enumerate() {
+        let mut cells = row
+            .iter()
+            .map(|cell| strip_ansi(cell.as_ref()).trim().to_string())
+            .collect::<Vec<_>>();
+        if i == 0 {
+            occupancy_column = cells.iter().position(|cell| cell == "Occupancy");
+        } else if let Some(column) = occupancy_column {
+            cells[column] = occupancy_of_bar(&cells[column]).to_string();
+        }
+        writer.write_record(cells)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Removes ANSI escape sequences like the colors of line labels.
+fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+const TIME_FORMATS: [&str; 3] = ["%H:%M", "%I:%M %p", "%I:%M%p"];
+
+/// Parses a time of day in 24-hour ("20:00") or 12-hour ("8:00 PM") format.
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(time.trim(), format).ok())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid time '{}', expected a time of day like '20:00' or '8:00 PM', \
+                 or a time from now like '+20m', '+1h30m' or 'in 45min'",
+                time
+            )
+        })
+}
+
+const DATE_FORMATS: [&str; 2] = ["%Y-%m-%d", "%d.%m.%Y"];
+
+/// Parses a date in ISO ("2024-06-01") or German ("01.06.2024") format.
+fn parse_date(date: &str) -> Result<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date.trim(), format).ok())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid date '{}', expected e.g. '2024-06-01' or '01.06.2024'",
+                date
+            )
+        })
+}
+
+/// The time of departure, or of arrival with `--arrival`, that connections
+/// are requested for, from `--time` and `--date` or else `now`.
+fn routing_time(args: &RoutesArgs, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let date = args.date.as_deref().map(parse_date).transpose()?;
+    Ok(match &args.time {
+        Some(t) => match parse_relative_time(t) {
+            Some(_) if date.is_some() => {
+                anyhow::bail!(
+                    "--date cannot be combined with a relative time like '{}'",
+                    t
+                )
+            }
+            Some(offset) => now
+                .checked_add_signed(offset)
+                .ok_or_else(|| anyhow::anyhow!("Time '{}' is too far in the future", t))?,
+            None => {
+                let time_of_day = parse_time(t)?;
+                match date {
+                    Some(date) => local_datetime(date, time_of_day)?,
+                    None => next_occurrence(time_of_day, now)?,
+                }
+            }
+        },
+        None => match date {
+            Some(date) => local_datetime(date, now.time())?,
+            None => now,
+        },
+    })
+}
+
+/// Combines a time of day with today's date, or with tomorrow's if it has
+/// already passed, so `--time 00:30` at 23:00 means half past midnight
+/// tonight. Times are compared by the minute, so `--time 14:00` at 14:00:30
+/// still means today. This holds for arrival times as well: a connection
+/// arriving before now cannot be taken, while one arriving later today is
+/// kept even if it departs before now.
+fn next_occurrence(time: NaiveTime, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let mut date = now.date_naive();
+    let current_minute = now
+        .naive_local()
+        .with_second(0)
+        .and_then(|t| t.with_nanosecond(0))
+        .unwrap();
+    if date.and_time(time) < current_minute {
+        date = date.succ_opt().unwrap();
+    }
+    local_datetime(date, time)
+}
+
+/// The local time at `time` on `date`, the earlier one if the clocks go back
+/// then. Fails for times skipped as the clocks go forward, e.g. 02:30 on the
+/// last Sunday in March.
+fn local_datetime(date: NaiveDate, time: NaiveTime) -> Result<DateTime<Local>> {
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} {} does not exist in the local time zone, as the clocks go forward then",
+                date,
+                time.format("%H:%M")
+            )
+        })
+}
+
+/// Parses a time relative to now, e.g. "+20m", "+1h30m" or "in 45min".
+fn parse_relative_time(time: &str) -> Option<chrono::Duration> {
+    let time = time.trim().to_lowercase();
+    let mut rest = time
+        .strip_prefix('+')
+        .or_else(|| time.strip_prefix("in "))?
+        .trim_start();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut minutes: u64 = 0;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let value: u64 = rest[..digits].parse().ok()?;
+        let unit_start = rest[digits..].trim_start();
+        let unit_len = unit_start
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(unit_start.len());
+        let factor = match &unit_start[..unit_len] {
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60,
+            "m" | "min" | "mins" | "minute" | "minutes" => 1,
+            _ => return None,
+        };
+        minutes = minutes.checked_add(value.checked_mul(factor)?)?;
+        rest = unit_start[unit_len..].trim_start();
+    }
+    chrono::Duration::from_std(Duration::from_secs(minutes.checked_mul(60)?)).ok()
+}
+
+/// Looks up stations, addresses and POIs, from the station cache unless
+/// `--no-cache` is given.
+async fn find_locations(ctx: &Context, query: &str) -> Result<Vec<Location>> {
+    Ok(match &ctx.station_cache {
+        Some(path) => get_station_cached(&ctx.client, query, path).await?,
+        None => get_station_with_client(&ctx.client, query).await?,
+    })
+}
+
+/// How long typing has to pause before the stations are looked up.
+const PICKER_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Lets the user search for a station like in `fzf`: the stations matching
+/// the query are looked up while typing, once typing pauses for
+/// [`PICKER_DEBOUNCE`], and one of them is selected with the arrow keys and
+/// Enter. Results are cached per query, so going back to an earlier query
+/// does not send another request. `dialoguer::FuzzySelect` only filters the
+/// items it was created with, so it cannot show results looked up while
+/// typing, and the picker uses crossterm like `--interactive` instead.
+async fn pick_station(ctx: &Context) -> Result<Location> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        anyhow::bail!("No station given");
+    }
+    terminal::enable_raw_mode()?;
+    print!("\x1b[?25l");
+    let picked = run_picker(ctx).await;
+    print!("\x1b[2J\x1b[H\x1b[?25h");
+    std::io::stdout().flush()?;
+    terminal::disable_raw_mode()?;
+    picked?.context("No station selected")
+}
+
+/// The event loop of [`pick_station`], run in raw mode. Returns `None` if
+/// the picker is cancelled with Esc or Ctrl-C.
+async fn run_picker(ctx: &Context) -> Result<Option<Location>> {
+    let mut cache: HashMap<String, Vec<Location>> = HashMap::new();
+    let mut query = String::new();
+    let mut stations: Vec<Location> = Vec::new();
+    let mut selected = 0;
+    let mut status = String::new();
+    let mut edited: Option<std::time::Instant> = None;
+    loop {
+        draw_picker(&query, &stations, selected, &status)?;
+        let timeout = match edited {
+            Some(edited) => PICKER_DEBOUNCE.saturating_sub(edited.elapsed()),
+            None => Duration::from_secs(60),
+        };
+        if !event::poll(timeout)? {
+            if edited.take().is_none() {
+                continue;
+            }
+            let key = query.trim().to_lowercase();
+            stations.clear();
+            selected = 0;
+            status.clear();
+            if key.is_empty() {
+                continue;
+            }
+            if !cache.contains_key(&key) {
+                match find_locations(ctx, query.trim()).await {
+                    Ok(locations) => {
+                        cache.insert(key.clone(), locations);
+                    }
+                    Err(e) => {
+                        status = format!("Search failed: {}", e);
+                        continue;
+                    }
+                }
+            }
+            stations = cache[&key]
+                .iter()
+                .filter(|l| matches!(l, Location::Station(_)))
+                .cloned()
+                .collect();
+            if stations.is_empty() {
+                status = "No stations found".to_string();
+            }
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
         }
-        _ => None,
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Enter if edited.is_none() => {
+                if let Some(station) = stations.get(selected) {
+                    return Ok(Some(station.clone()));
+                }
+            }
+            KeyCode::Up => selected = selected.saturating_sub(1),
+            KeyCode::Down if selected + 1 < stations.len() => selected += 1,
+            KeyCode::Char(c) => {
+                query.push(c);
+                edited = Some(std::time::Instant::now());
+            }
+            KeyCode::Backspace => {
+                query.pop();
+                edited = Some(std::time::Instant::now());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Draws the query, the stations found for it with the selected one marked,
+/// and `status`, with `\r\n` line breaks for raw mode.
+fn draw_picker(query: &str, stations: &[Location], selected: usize, status: &str) -> Result<()> {
+    let mut out = std::io::stdout();
+    write!(out, "\x1b[2J\x1b[H")?;
+    write!(out, "Search station: {}\r\n", query)?;
+    for (i, location) in stations.iter().enumerate() {
+        let marker = if i == selected { ">" } else { " " };
+        write!(
+            out,
+            "{} {}, {}\r\n",
+            marker,
+            location.name(),
+            location.place()
+        )?;
+    }
+    if !status.is_empty() {
+        write!(out, "{}\r\n", status)?;
+    }
+    write!(out, "[↑/↓] select  [enter] confirm  [esc] cancel\r\n")?;
+    out.flush()?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LocationMode {
+    Auto,
+    Address,
+    Station,
+}
+
+/// Heuristic for `LocationMode::Auto`: queries containing a digit or a comma,
+/// like "Marienplatz 1, München", are treated as addresses.
+fn looks_like_address(query: &str) -> bool {
+    query.chars().any(|c| c.is_ascii_digit() || c == ',')
+}
+
+async fn resolve_location(
+    ctx: &Context,
+    query: &str,
+    mode: LocationMode,
+    place: Option<&str>,
+) -> Result<Location> {
+    let locations = find_locations(ctx, query).await?;
+    if let Some(place) = place {
+        return select_by_place(locations, query, place);
+    }
+    let want_address = match mode {
+        LocationMode::Auto => looks_like_address(query),
+        LocationMode::Address => true,
+        LocationMode::Station => false,
+    };
+    let address = locations
+        .iter()
+        .position(|l| matches!(l, Location::Address(_)));
+    let station = locations
+        .iter()
+        .position(|l| matches!(l, Location::Station(_)));
+    let index = match (mode, want_address) {
+        (LocationMode::Auto, true) => address.or(station),
+        (_, true) => address,
+        (_, false) => station,
+    };
+    match index {
+        Some(i) if matches!(locations[i], Location::Station(_)) => choose_station(locations, query),
+        Some(i) => Ok(locations.into_iter().nth(i).unwrap()),
+        None if want_address => {
+            anyhow::bail!(Failure::UnknownStation(format!(
+                "No address {} found",
+                query
+            )))
+        }
+        None => Err(station_not_found(query, &locations)),
+    }
+}
+
+/// The error for a search without a matching station, with up to three "did
+/// you mean" suggestions from the locations the search returned whose names
+/// are within a few edits of `query`.
+fn station_not_found(query: &str, locations: &[Location]) -> anyhow::Error {
+    let query_lower = query.trim().to_lowercase();
+    let max_distance = (query_lower.chars().count() / 3).max(2);
+    let mut candidates = locations
+        .iter()
+        .map(|l| {
+            let distance = edit_distance(&query_lower, &l.name().to_lowercase());
+            (distance, format!("{}, {}", l.name(), l.place()))
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|(distance, _)| *distance);
+    candidates.dedup_by(|a, b| a.1 == b.1);
+    let suggestions = candidates
+        .into_iter()
+        .take(3)
+        .map(|(_, name)| name)
+        .collect::<Vec<_>>();
+    if suggestions.is_empty() {
+        Failure::UnknownStation(format!("No station {} found", query)).into()
+    } else {
+        Failure::UnknownStation(format!(
+            "No station {} found. Did you mean {}?",
+            query,
+            suggestions.join(" or ")
+        ))
+        .into()
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Selects the station named exactly like `query` or, if there are several
+/// or none, lets the user choose between them. Without a terminal, the first
+/// station is taken.
+fn choose_station(locations: Vec<Location>, query: &str) -> Result<Location> {
+    if !locations.iter().any(|l| matches!(l, Location::Station(_))) {
+        return Err(station_not_found(query, &locations));
+    }
+    let mut stations = locations
+        .into_iter()
+        .filter(|l| matches!(l, Location::Station(_)))
+        .collect::<Vec<_>>();
+    let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    if !interactive {
+        return Ok(stations.swap_remove(0));
+    }
+    let is_exact = |l: &Location| l.name().eq_ignore_ascii_case(query);
+    if stations.iter().any(is_exact) {
+        stations.retain(is_exact);
+    }
+    if stations.len() == 1 {
+        return Ok(stations.swap_remove(0));
+    }
+    for (i, location) in stations.iter().enumerate() {
+        println!("[{}] {}, {}", i + 1, location.name(), location.place());
+    }
+    loop {
+        print!("Which station? ");
+        std::io::stdout().flush()?;
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input)? == 0 {
+            anyhow::bail!("No station selected");
+        }
+        match input.trim().parse::<usize>() {
+            Ok(n) if (1..=stations.len()).contains(&n) => return Ok(stations.swap_remove(n - 1)),
+            _ => println!("Enter a number between 1 and {}", stations.len()),
+        }
+    }
+}
+
+/// Selects the one station whose place contains `place`, preferring stations
+/// named exactly like `query` if there are several.
+fn select_by_place(locations: Vec<Location>, query: &str, place: &str) -> Result<Location> {
+    let place_lower = place.to_lowercase();
+    let mut stations = locations
+        .into_iter()
+        .filter(|l| match l {
+            Location::Station(s) => s.place.to_lowercase().contains(&place_lower),
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    let is_exact = |l: &Location| l.name().eq_ignore_ascii_case(query);
+    if stations.len() > 1 && stations.iter().any(is_exact) {
+        stations.retain(is_exact);
+    }
+    match stations.len() {
+        0 => anyhow::bail!(Failure::UnknownStation(format!(
+            "No station {} found in {}",
+            query, place
+        ))),
+        1 => Ok(stations.swap_remove(0)),
+        _ => {
+            let names = stations
+                .iter()
+                .map(|l| format!("{}, {}", l.name(), l.place()))
+                .collect::<Vec<_>>()
+                .join("; ");
+            anyhow::bail!("Station {} in {} is ambiguous: {}", query, place, names)
+        }
+    }
+}
+
+fn route_point_from_location(location: &Location) -> RoutePoint {
+    match location {
+        Location::Station(s) => RoutePoint::Station(s.global_id.clone()),
+        Location::Address(a) => RoutePoint::Coordinates {
+            latitude: a.latitude,
+            longitude: a.longitude,
+        },
+        Location::Poi(p) => RoutePoint::Coordinates {
+            latitude: p.latitude,
+            longitude: p.longitude,
+        },
+    }
+}
+
+/// The name of the location for status lines, followed by the global id of
+/// stations if `print_ids` is set.
+fn status_name(location: &Location, print_ids: bool) -> String {
+    let name = name_from_location(location);
+    match location.id() {
+        Some(id) if print_ids => format!("{} ({})", name, id),
+        _ => name,
+    }
+}
+
+/// The display name of any location: the name of a station or POI, or the
+/// street address, each followed by its place.
+fn name_from_location(location: &Location) -> String {
+    let place = match location {
+        Location::Address(a) => format!("{} {}", a.post_code, a.place),
+        _ => location.place().to_string(),
+    };
+    let name = paint(Style::new().bold(), location.name());
+    let place = paint(Style::new().italic(), place.trim());
+    [name, place].join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    fn routes_args(args: &[&str]) -> RoutesArgs {
+        let argv = ["mvg", "routes", "Marienplatz", "Goetheplatz"];
+        match Cli::parse_from(argv.iter().chain(args)).command {
+            Some(Commands::Routes(args)) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    fn departures_args() -> DeparturesArgs {
+        match Cli::parse_from(["mvg", "departures", "Marienplatz"]).command {
+            Some(Commands::Departures(args)) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    fn connection_fixture() -> Value {
+        serde_json::from_str(include_str!("../mvg-api/tests/fixtures/connection.json")).unwrap()
+    }
+
+    /// The plain cells of the column `name`, without its header.
+    fn column(table: &Table, name: &str) -> Vec<String> {
+        let records = table.get_records();
+        let rows = records
+            .iter()
+            .map(|row| row.iter().map(|cell| strip_ansi(cell.as_ref())).collect())
+            .collect::<Vec<Vec<String>>>();
+        let index = rows[0].iter().position(|header| header == name).unwrap();
+        rows[1..].iter().map(|row| row[index].clone()).collect()
+    }
+
+    fn late_evening() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 16, 23, 55, 0).unwrap()
+    }
+
+    #[test]
+    fn routes_count_minutes_across_midnight() {
+        let now = late_evening();
+        let departure = (now + chrono::Duration::minutes(15)).to_rfc3339();
+        let mut fixture = connection_fixture();
+        fixture["parts"][0]["from"]["plannedDeparture"] = Value::from(departure);
+        let routes = [serde_json::from_value::<Connection>(fixture).unwrap()];
+        let table = routes_table(&routes, &routes_args(&[]), now, &HashSet::new());
+        assert_eq!(column(&table, "In"), ["15"]);
+    }
+
+    #[test]
+    fn departures_count_minutes_across_midnight() {
+        let now = late_evening();
+        let departure = (now + chrono::Duration::minutes(15)).timestamp_millis();
+        let mut fixture: Vec<Value> =
+            serde_json::from_str(include_str!("../mvg-api/tests/fixtures/departures.json"))
+                .unwrap();
+        fixture.truncate(1);
+        fixture[0]["plannedDepartureTime"] = Value::from(departure);
+        fixture[0]["realtimeDepartureTime"] = Value::from(departure);
+        let departures = serde_json::from_value::<Vec<Departure>>(Value::from(fixture)).unwrap();
+        let table = departures_table(&departures, &departures_args(), now, None);
+        assert_eq!(column(&table, "In"), ["15"]);
+    }
+
+    fn locations_fixture() -> Vec<Location> {
+        serde_json::from_str(include_str!("../mvg-api/tests/fixtures/locations.json")).unwrap()
+    }
+
+    #[test]
+    fn names_every_kind_of_location() {
+        let names = locations_fixture()
+            .iter()
+            .map(|location| strip_ansi(&name_from_location(location)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "Münchner Freiheit, München",
+                "Leopoldstraße 1, 80802 München",
+                "Englischer Garten, München",
+            ]
+        );
+    }
+
+    fn at(hour: u32, minute: u32, second: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 10, 16, hour, minute, second)
+            .unwrap()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn departure_time_passed_today_means_tomorrow() {
+        let planned = next_occurrence(time(0, 30), at(23, 0, 0)).unwrap();
+        assert_eq!(planned, at(0, 30, 0) + chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn departure_time_later_today_means_today() {
+        assert_eq!(
+            next_occurrence(time(23, 30), at(23, 0, 0)).unwrap(),
+            at(23, 30, 0)
+        );
+    }
+
+    #[test]
+    fn current_minute_means_today() {
+        assert_eq!(
+            next_occurrence(time(14, 0), at(14, 0, 30)).unwrap(),
+            at(14, 0, 0)
+        );
+        assert_eq!(
+            next_occurrence(time(13, 59), at(14, 0, 30)).unwrap(),
+            at(13, 59, 0) + chrono::Duration::days(1)
+        );
+    }
+
+    #[test]
+    fn arrival_time_later_today_means_today() {
+        // With --arrival, a connection arriving at 18:00 is requested for
+        // today even though it may depart before now.
+        let args = routes_args(&["--time", "18:00", "--arrival"]);
+        assert!(args.arrival);
+        assert_eq!(routing_time(&args, at(14, 0, 0)).unwrap(), at(18, 0, 0));
+    }
+
+    #[test]
+    fn arrival_time_passed_today_means_tomorrow() {
+        let args = routes_args(&["--time", "08:00", "--arrival"]);
+        assert!(args.arrival);
+        assert_eq!(
+            routing_time(&args, at(14, 0, 0)).unwrap(),
+            at(8, 0, 0) + chrono::Duration::days(1)
+        );
+    }
+
+    #[test]
+    fn dedup_connections_keeps_differently_ticketed_duplicates() {
+        let connection = || serde_json::from_value::<Connection>(connection_fixture()).unwrap();
+        let mut routes = vec![connection(), connection()];
+        dedup_connections(&mut routes, false);
+        assert_eq!(routes.len(), 1);
+
+        let mut fixture = connection_fixture();
+        fixture["ticketingInformation"]["zones"] = Value::from(vec![0, 1]);
+        let mut routes = vec![
+            connection(),
+            serde_json::from_value::<Connection>(fixture).unwrap(),
+        ];
+        dedup_connections(&mut routes, false);
+        assert_eq!(routes.len(), 2);
+    }
+
+    #[test]
+    fn edit_distance_counts_characters() {
+        assert_eq!(edit_distance("muenchner", "münchner"), 2);
+        assert_eq!(edit_distance("marienplatz", "marienplatz"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn unknown_station_suggests_similar_names() {
+        let error = station_not_found("Muenchner Freiheit", &locations_fixture());
+        assert_eq!(
+            error.to_string(),
+            "No station Muenchner Freiheit found. Did you mean Münchner Freiheit, München?"
+        );
+        assert_eq!(exit_code(&error), 3);
+    }
+
+    #[test]
+    fn unknown_station_without_similar_names() {
+        let error = station_not_found("Xyz", &[]);
+        assert_eq!(error.to_string(), "No station Xyz found");
+        assert_eq!(exit_code(&error), 3);
+    }
+
+    #[test]
+    fn max_walk_drops_connections_with_longer_walks() {
+        let connection = || serde_json::from_value::<Connection>(connection_fixture()).unwrap();
+        let max_walk = routes_args(&["--max-walk", "500"]).max_walk.unwrap();
+        let mut routes = vec![connection()];
+        retain_max_walk(&mut routes, max_walk);
+        assert!(routes.is_empty());
+
+        let mut routes = vec![connection()];
+        retain_max_walk(&mut routes, 1000.0);
+        assert_eq!(routes.len(), 1);
+    }
+
+    #[test]
+    fn last_keeps_flags_given_now() {
+        let argv = ["mvg", "--json", "last"].map(String::from);
+        let last_args = ["--format", "csv", "d", "Marienplatz"]
+            .map(String::from)
+            .to_vec();
+        let argv = last_argv(&argv, last_args);
+        assert_eq!(
+            argv,
+            ["mvg", "--format", "csv", "d", "Marienplatz", "--json"]
+        );
+        let args = Cli::parse_from(&argv);
+        assert!(args.json);
+        assert!(matches!(args.command, Some(Commands::Departures(_))));
+    }
+
+    #[test]
+    fn flags_given_now_override_stored_ones() {
+        let argv = ["mvg", "last", "--format", "tsv"].map(String::from);
+        let last_args = ["--format", "csv", "d", "Marienplatz"]
+            .map(String::from)
+            .to_vec();
+        let args = Cli::parse_from(last_argv(&argv, last_args));
+        assert_eq!(args.format, OutputFormat::Tsv);
+    }
+
+    #[test]
+    fn empty_result_is_nothing_found() {
+        let error = ensure_found::<Connection>(&[], "connection").unwrap_err();
+        assert_eq!(error.to_string(), "No connection found");
+        assert_eq!(exit_code(&error), 4);
+        assert!(ensure_found(&[()], "departure").is_ok());
+    }
+
+    #[test]
+    fn csv_cells_are_plain() {
+        let departures: Vec<Departure> =
+            serde_json::from_str(include_str!("../mvg-api/tests/fixtures/departures.json"))
+                .unwrap();
+        let table = departures_table(&departures, &departures_args(), late_evening(), None);
+        let mut csv = Vec::new();
+        write_delimited(&table, b',', &mut csv).unwrap();
+        let mut reader = csv::Reader::from_reader(csv.as_slice());
+        let headers = reader.headers().unwrap().clone();
+        let line = headers.iter().position(|h| h == "Line").unwrap();
+        let occupancy = headers.iter().position(|h| h == "Occupancy").unwrap();
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(&rows[0][line], "U6");
+        assert_eq!(&rows[0][occupancy], "LOW");
+        assert_eq!(&rows[1][line], "52");
+        assert_eq!(&rows[1][occupancy], "UNKNOWN");
     }
 }